@@ -1,15 +1,405 @@
 use crate::config::Config;
+use crate::error::AppError;
+use crate::locks::LockExt;
+use serde::{Deserialize, Serialize};
 use std::process::{Command, Stdio, Child};
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
 use once_cell::sync::Lazy;
 use std::thread;
 use rand::Rng;
 
-static LOCAL_SERVER_PROCESS: Lazy<Mutex<Option<Child>>> = Lazy::new(|| Mutex::new(None));
-static TUNNEL_PROCESS: Lazy<Mutex<Option<Child>>> = Lazy::new(|| Mutex::new(None));
-static BRIDGE_PROCESS: Lazy<Mutex<Option<Child>>> = Lazy::new(|| Mutex::new(None));
+/// Every process the app manages
+static PROCESSES: Lazy<ProcessRegistry> = Lazy::new(ProcessRegistry::default);
+
+/// How long cloudflared gets to report its URL, unless `timeouts.tunnelUrlTimeoutSecs` says otherwise
+const TUNNEL_URL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wakes a `start_tunnel_and_get_url` that is still waiting for its URL
+static TUNNEL_CANCEL: Lazy<tokio::sync::Notify> = Lazy::new(tokio::sync::Notify::new);
+
+/// Number of log lines included in diagnostics
+const LOG_TAIL_LINES: usize = 50;
+
+/// The long-running processes the app manages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProcessKind {
+    LocalServer,
+    Tunnel,
+    Bridge,
+}
+
+impl ProcessKind {
+    pub const ALL: [ProcessKind; 3] = [ProcessKind::LocalServer, ProcessKind::Tunnel, ProcessKind::Bridge];
+
+    /// Name used for the process's log file
+    pub fn log_name(self) -> &'static str {
+        match self {
+            ProcessKind::LocalServer => "wrangler",
+            ProcessKind::Tunnel => "tunnel",
+            ProcessKind::Bridge => "bridge",
+        }
+    }
+
+    /// Inverse of `log_name`, for log sources named by the frontend
+    pub fn from_log_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.log_name() == name)
+    }
+}
+
+/// A running process we spawned
+struct ManagedProcess {
+    child: Child,
+    /// Program and arguments it was started with
+    command: String,
+    log_path: Option<PathBuf>,
+}
+
+/// What the registry holds for one kind of process
+#[derive(Default)]
+struct ProcessEntry {
+    process: Option<ManagedProcess>,
+    /// Most recent exit status, written by the crash watcher and `stop`
+    last_exit: Option<std::process::ExitStatus>,
+    /// Times this process has been spawned since the stats were last reset
+    starts: u32,
+    first_started: Option<std::time::SystemTime>,
+}
+
+/// The managed processes, one per `ProcessKind`, with what's known about each
+#[derive(Default)]
+struct ProcessRegistry {
+    entries: Mutex<HashMap<ProcessKind, ProcessEntry>>,
+}
+
+impl ProcessRegistry {
+    /// Track a freshly spawned child as the process for `kind`
+    fn register(&self, kind: ProcessKind, child: Child, command: String) {
+        let process = ManagedProcess { child, command, log_path: log_path(kind) };
+        let mut entries = self.entries.lock_or_recover();
+        let entry = entries.entry(kind).or_default();
+        entry.process = Some(process);
+        entry.starts += 1;
+        entry.first_started.get_or_insert_with(std::time::SystemTime::now);
+    }
+
+    /// How many times `kind` was spawned again after its first start
+    fn restarts(&self, kind: ProcessKind) -> u32 {
+        let entries = self.entries.lock_or_recover();
+        entries.get(&kind).map_or(0, |entry| entry.starts.saturating_sub(1))
+    }
+
+    /// When the earliest process was first started since the stats were reset
+    fn started_at(&self) -> Option<std::time::SystemTime> {
+        self.entries.lock_or_recover().values().filter_map(|entry| entry.first_started).min()
+    }
+
+    /// Forget start times and restart counts, keeping any processes running
+    fn reset_stats(&self) {
+        for entry in self.entries.lock_or_recover().values_mut() {
+            entry.starts = 0;
+            entry.first_started = None;
+        }
+    }
+
+    /// Stop tracking the process for `kind` and hand its child back
+    fn take(&self, kind: ProcessKind) -> Option<Child> {
+        let mut entries = self.entries.lock_or_recover();
+        entries.get_mut(&kind)?.process.take().map(|process| process.child)
+    }
+
+    fn pid(&self, kind: ProcessKind) -> Option<u32> {
+        let entries = self.entries.lock_or_recover();
+        entries.get(&kind)?.process.as_ref().map(|process| process.child.id())
+    }
+
+    /// PIDs of every tracked process
+    fn pids(&self) -> Vec<u32> {
+        ProcessKind::ALL.iter().filter_map(|&kind| self.pid(kind)).collect()
+    }
+
+    fn command(&self, kind: ProcessKind) -> Option<String> {
+        let entries = self.entries.lock_or_recover();
+        entries.get(&kind)?.process.as_ref().map(|process| process.command.clone())
+    }
+
+    fn log_path(&self, kind: ProcessKind) -> Option<PathBuf> {
+        let entries = self.entries.lock_or_recover();
+        entries.get(&kind)?.process.as_ref()?.log_path.clone()
+    }
+
+    fn is_running(&self, kind: ProcessKind) -> bool {
+        let mut entries = self.entries.lock_or_recover();
+        match entries.get_mut(&kind).and_then(|entry| entry.process.as_mut()) {
+            Some(process) => matches!(process.child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+
+    /// Whether the local server is fully up: the bridge, plus wrangler unless it's local-only
+    fn server_running(&self, local_only: bool) -> bool {
+        self.is_running(ProcessKind::Bridge) && (local_only || self.is_running(ProcessKind::LocalServer))
+    }
+
+    fn last_exit(&self, kind: ProcessKind) -> Option<std::process::ExitStatus> {
+        self.entries.lock_or_recover().get(&kind)?.last_exit
+    }
+
+    fn record_exit(&self, kind: ProcessKind, status: std::process::ExitStatus) {
+        self.entries.lock_or_recover().entry(kind).or_default().last_exit = Some(status);
+    }
+
+    /// If the process for `kind` has exited on its own, stop tracking it and return how it ended
+    fn reap(&self, kind: ProcessKind) -> Option<std::process::ExitStatus> {
+        let mut entries = self.entries.lock_or_recover();
+        let entry = entries.get_mut(&kind)?;
+        let status = entry.process.as_mut()?.child.try_wait().ok().flatten()?;
+        entry.process = None;
+        entry.last_exit = Some(status);
+        Some(status)
+    }
+
+    /// Stop the process for `kind` and its whole process group
+    /// Returns whether anything was being tracked
+    fn stop(&self, kind: ProcessKind) -> bool {
+        // Take the child first so the slow shutdown below doesn't hold the lock
+        let Some(mut child) = self.take(kind) else {
+            return false;
+        };
+        let pgid = child.id();
+        signal_group(pgid, "-TERM");
+        // A paused group can't act on TERM until it's continued
+        signal_group(pgid, "-CONT");
+        
+        // Give the tree a moment to shut down cleanly before forcing it
+        for _ in 0..10 {
+            if matches!(child.try_wait(), Ok(Some(_))) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        signal_group(pgid, "-KILL");
+        
+        let _ = child.kill();
+        if let Ok(status) = child.wait() {
+            self.record_exit(kind, status);
+        }
+        true
+    }
+}
+
+/// Whether a managed process is currently alive
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessStatus {
+    pub name: ProcessKind,
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// What it was started as, e.g. `npx wrangler dev --ip 127.0.0.1 --port 8787`
+    pub command: Option<String>,
+    pub log_path: Option<PathBuf>,
+    /// Respawns since the system was started, whether by boot retries or restarts
+    pub restarts: u32,
+}
+
+/// What we know about a managed process, for bug reports
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessDiagnostics {
+    pub name: ProcessKind,
+    pub running: bool,
+    pub last_exit_code: Option<i32>,
+    pub last_signal: Option<i32>,
+    pub restarts: u32,
+    pub log_tail: Vec<String>,
+}
+
+/// Directory holding all log files, created on demand
+fn logs_dir() -> Option<PathBuf> {
+    let dir = crate::config::get_config_dir().ok()?.join("logs");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// Path of the log file a process's output is written to
+fn log_path(kind: ProcessKind) -> Option<PathBuf> {
+    Some(logs_dir()?.join(format!("{}.log", kind.log_name())))
+}
+
+/// How log records we write ourselves are formatted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable lines (the default)
+    Text,
+    /// One JSON object per line, for `jq` and log shippers
+    Json,
+}
+
+/// Log format chosen with `SYSTEM_LOG_FORMAT=json`
+fn log_format() -> LogFormat {
+    match std::env::var("SYSTEM_LOG_FORMAT") {
+        Ok(v) if v.eq_ignore_ascii_case("json") => LogFormat::Json,
+        _ => LogFormat::Text,
+    }
+}
+
+/// UTC RFC 3339 timestamp with milliseconds
+fn format_timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, rem / 3_600, rem % 3_600 / 60, rem % 60, since_epoch.subsec_millis()
+    )
+}
+
+/// Render one log record in the given format
+fn format_log_record(format: LogFormat, ts: &str, level: &str, source: &str, msg: &str) -> String {
+    match format {
+        LogFormat::Text => format!("{} {:<5} [{}] {}", ts, level.to_uppercase(), source, msg),
+        LogFormat::Json => serde_json::json!({
+            "ts": ts,
+            "level": level,
+            "source": source,
+            "msg": msg,
+        })
+        .to_string(),
+    }
+}
+
+/// Record an app event in system.log and on stderr
+pub fn log(level: &str, source: &str, msg: &str) {
+    let record = format_log_record(log_format(), &format_timestamp(std::time::SystemTime::now()), level, source, msg);
+    eprintln!("{}", record);
+    
+    let file = logs_dir().and_then(|dir| {
+        std::fs::OpenOptions::new().create(true).append(true).open(dir.join("system.log")).ok()
+    });
+    if let Some(mut file) = file {
+        let _ = writeln!(file, "{}", record);
+    }
+}
+
+/// Write a line of subprocess output to its log, wrapping it in a record in JSON mode
+fn write_output_line(file: &mut File, source: &str, line: &str) {
+    let _ = match log_format() {
+        LogFormat::Text => writeln!(file, "{}", line),
+        LogFormat::Json => writeln!(
+            file,
+            "{}",
+            format_log_record(LogFormat::Json, &format_timestamp(std::time::SystemTime::now()), "info", source, line)
+        ),
+    };
+}
+
+/// Start a fresh log file for a process
+fn open_log(kind: ProcessKind) -> Option<File> {
+    log_path(kind).and_then(|path| File::create(path).ok())
+}
+
+/// Stdio handles that send a process's stdout and stderr to its log
+fn log_stdio(kind: ProcessKind) -> (Stdio, Stdio) {
+    match open_log(kind) {
+        Some(file) => match file.try_clone() {
+            Ok(clone) => (Stdio::from(file), Stdio::from(clone)),
+            Err(_) => (Stdio::from(file), Stdio::null()),
+        },
+        None => (Stdio::null(), Stdio::null()),
+    }
+}
+
+/// Last lines of a process's log
+pub fn log_tail(kind: ProcessKind, lines: usize) -> Vec<String> {
+    let content = log_path(kind)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    let all: Vec<&str> = content.lines().collect();
+    all[all.len().saturating_sub(lines)..].iter().map(|l| l.to_string()).collect()
+}
+
+/// Stop flags for the threads following each log, keyed by process
+static LOG_FOLLOWERS: Lazy<Mutex<HashMap<ProcessKind, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// How often a follower checks its log for new output
+const LOG_FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Complete lines appended to `path` since byte `offset`, advancing `offset` past them
+/// A file shorter than `offset` was truncated by a respawn, so reading restarts from the top
+fn read_new_lines(path: &std::path::Path, offset: &mut u64) -> Vec<String> {
+    use std::io::{Read, Seek, SeekFrom};
+    
+    let Ok(mut file) = File::open(path) else { return Vec::new() };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if len < *offset {
+        *offset = 0;
+    }
+    if file.seek(SeekFrom::Start(*offset)).is_err() {
+        return Vec::new();
+    }
+    
+    let mut buf = Vec::new();
+    if file.read_to_end(&mut buf).is_err() {
+        return Vec::new();
+    }
+    // Leave a partial last line for the next read
+    let complete = buf.iter().rposition(|&b| b == b'\n').map(|i| i + 1).unwrap_or(0);
+    *offset += complete as u64;
+    String::from_utf8_lossy(&buf[..complete]).lines().map(String::from).collect()
+}
+
+/// Current tail of a process's log, then (if `follow`) every new line through `on_line`
+/// until `unfollow_log` is called; following again replaces the previous follower
+pub fn tail_log(kind: ProcessKind, follow: bool, on_line: impl Fn(&str) + Send + 'static) -> Vec<String> {
+    let tail = log_tail(kind, LOG_TAIL_LINES);
+    if !follow {
+        return tail;
+    }
+    let Some(path) = log_path(kind) else { return tail };
+    
+    let running = Arc::new(AtomicBool::new(true));
+    if let Some(previous) = LOG_FOLLOWERS.lock_or_recover().insert(kind, Arc::clone(&running)) {
+        previous.store(false, Ordering::Relaxed);
+    }
+    
+    let mut offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            for line in read_new_lines(&path, &mut offset) {
+                on_line(&line);
+            }
+            thread::sleep(LOG_FOLLOW_INTERVAL);
+        }
+    });
+    tail
+}
+
+/// Stop following a process's log
+pub fn unfollow_log(kind: ProcessKind) {
+    if let Some(running) = LOG_FOLLOWERS.lock_or_recover().remove(&kind) {
+        running.store(false, Ordering::Relaxed);
+    }
+}
 
 /// Generate a secure random token for API authentication
 pub fn generate_token() -> String {
@@ -23,8 +413,7 @@ pub fn generate_token() -> String {
         .collect()
 }
 
-fn get_path_env() -> String {
-    let _home = std::env::var("HOME").unwrap_or_default();
+fn get_path_env(config: &Config) -> String {
     let existing_path = std::env::var("PATH").unwrap_or_default();
     
     let paths = [
@@ -36,7 +425,15 @@ fn get_path_env() -> String {
         "/sbin",
     ];
     
-    let mut path_vec: Vec<&str> = paths.to_vec();
+    // A pinned node's directory goes first so npx and scripts' `env node` agree with it
+    let pinned_dirs: Vec<String> = [&config.node_bin, &config.npx_bin]
+        .into_iter()
+        .flatten()
+        .filter_map(|bin| std::path::Path::new(bin).parent().map(|dir| dir.to_string_lossy().to_string()))
+        .collect();
+    
+    let mut path_vec: Vec<&str> = pinned_dirs.iter().map(String::as_str).collect();
+    path_vec.extend(paths);
     if !existing_path.is_empty() {
         path_vec.push(&existing_path);
     }
@@ -44,194 +441,3560 @@ fn get_path_env() -> String {
     path_vec.join(":")
 }
 
-fn create_command(program: &str) -> Command {
-    let mut cmd = Command::new(program);
-    cmd.env("PATH", get_path_env());
+/// Absolute path `create_command(name, config)` would run: the pinned binary if there is one, else the first hit on our PATH
+pub fn resolve_binary(name: &str, config: &Config) -> Option<PathBuf> {
+    let program = pinned_binary(name, config).unwrap_or_else(|| name.to_string());
+    resolve_in(&program, &get_path_env(config))
+}
+
+/// Find `program` the way execvp would: as given when it contains a slash, otherwise in each of `path`'s directories
+fn resolve_in(program: &str, path: &str) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let is_executable = |candidate: &std::path::Path| {
+        std::fs::metadata(candidate).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        let candidate = PathBuf::from(program);
+        return is_executable(&candidate).then_some(candidate);
+    }
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| std::path::Path::new(dir).join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Why spawning `program` failed, naming what it resolved to or every directory that was searched
+fn spawn_failure(program: &str, resolved: Option<&std::path::Path>, path: &str, err: &std::io::Error) -> String {
+    match resolved {
+        Some(bin) => format!("Resolved {} to {} but spawn failed: {}", program, bin.display(), err),
+        None => format!("{} not found in {}", program, path.split(':').filter(|d| !d.is_empty()).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// Spawn `cmd`, logging the binary `program` resolved to, with an error that says where it looked if it fails
+fn spawn_resolved(cmd: &mut Command, program: &str, config: &Config) -> std::io::Result<Child> {
+    let resolved = resolve_binary(program, config);
+    if let Some(ref bin) = resolved {
+        log("info", "system", &format!("Starting {} ({})", program, bin.display()));
+    }
+    cmd.spawn().map_err(|e| std::io::Error::new(e.kind(), spawn_failure(program, resolved.as_deref(), &get_path_env(config), &e)))
+}
+
+/// Oldest releases known to run quick tunnels and print the URL format we parse
+const TOOL_MINIMUMS: &[(&str, &str, &str)] = &[
+    ("cloudflared", "2024.1.0", "Update cloudflared with `brew upgrade cloudflared`"),
+    ("wrangler", "3.0.0", "Update wrangler with `npm install wrangler@latest` in cloudflare-agent"),
+];
+
+/// A CLI the system depends on, and whether its version is new enough
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolVersion {
+    pub tool: String,
+    /// None when the tool is missing or its output didn't contain a version
+    pub version: Option<String>,
+    /// First line of what `--version` printed, for versions we can't parse
+    pub raw: Option<String>,
+    pub minimum: String,
+    /// None when the version couldn't be determined
+    pub up_to_date: Option<bool>,
+    pub hint: Option<String>,
+}
+
+/// First dotted number in `--version` output, e.g. "2024.8.2" from "cloudflared version 2024.8.2 (built ...)"
+fn parse_tool_version(output: &str) -> Option<String> {
+    output
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| word.contains('.') && word.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())))
+        .map(String::from)
+}
+
+/// Judge a tool's `--version` output against its known-good minimum
+fn assess_tool_version(tool: &str, minimum: &str, hint: &str, output: Option<&str>) -> ToolVersion {
+    let version = output.and_then(parse_tool_version);
+    let up_to_date = version.as_deref().map(|v| version_at_least(v, minimum));
+    let hint = match (output, up_to_date) {
+        (None, _) => Some(format!("{} isn't installed or didn't run", tool)),
+        (Some(_), None) => Some(format!("Couldn't read {}'s version - if tunnels won't start, try updating it", tool)),
+        (_, Some(false)) => Some(hint.to_string()),
+        (_, Some(true)) => None,
+    };
+    ToolVersion {
+        tool: tool.to_string(),
+        version,
+        raw: output.and_then(|o| o.lines().find(|l| !l.trim().is_empty())).map(|l| l.trim().to_string()),
+        minimum: minimum.to_string(),
+        up_to_date,
+        hint,
+    }
+}
+
+/// Run `cloudflared --version` and the project's `wrangler --version`, flagging anything too old
+pub fn check_tool_versions() -> Vec<ToolVersion> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let agent_dir = find_project_root(Some(&config)).ok().map(|root| root.join("cloudflare-agent"));
+    
+    TOOL_MINIMUMS.iter().map(|&(tool, minimum, hint)| {
+        let mut cmd = match tool {
+            "wrangler" => {
+                // --no-install: report a missing wrangler rather than downloading one to check it
+                let mut cmd = create_command("npx", &config);
+                cmd.args(["--no-install", "wrangler", "--version"]);
+                if let Some(ref dir) = agent_dir {
+                    cmd.current_dir(dir);
+                }
+                cmd
+            }
+            _ => {
+                let mut cmd = create_command(tool, &config);
+                cmd.arg("--version");
+                cmd
+            }
+        };
+        let output = cmd.output().ok().filter(|o| o.status.success()).map(|o| {
+            // cloudflared prints its version to stdout, but older builds used stderr
+            format!("{}{}", String::from_utf8_lossy(&o.stdout), String::from_utf8_lossy(&o.stderr))
+        });
+        assess_tool_version(tool, minimum, hint, output.as_deref())
+    }).collect()
+}
+
+/// The user's pinned path for `node`/`npx`, if they set one
+fn pinned_binary(program: &str, config: &Config) -> Option<String> {
+    match program {
+        "node" => config.node_bin.clone(),
+        "npx" => config.npx_bin.clone(),
+        _ => None,
+    }
+}
+
+/// `program` as `config` pins it, with our PATH so it finds node and friends
+fn create_command(program: &str, config: &Config) -> Command {
+    let mut cmd = Command::new(pinned_binary(program, config).unwrap_or_else(|| program.to_string()));
+    cmd.env("PATH", get_path_env(config));
     cmd
 }
 
-pub fn find_project_root(config: Option<&Config>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
-    if let Some(cfg) = config {
-        if let Some(ref root) = cfg.project_root {
-            let path = PathBuf::from(root);
-            if path.join("cloudflare-agent").exists() {
-                return Ok(path);
+/// Directories never worth descending into when scanning for the project
+const SCAN_SKIP_DIRS: &[&str] = &["node_modules", "Library", "Applications", "Music", "Movies", "Pictures", "target", "dist"];
+
+/// Walk each base dir up to `max_depth` levels deep, collecting every directory that contains `cloudflare-agent`
+/// Hidden directories and `SCAN_SKIP_DIRS` are skipped, and matches aren't descended into
+pub fn scan_for_projects(bases: &[PathBuf], max_depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for base in bases {
+        let mut frontier = vec![base.clone()];
+        for depth in 0..=max_depth {
+            let mut next = Vec::new();
+            for dir in frontier {
+                if dir.join("cloudflare-agent").is_dir() {
+                    if !found.contains(&dir) {
+                        found.push(dir);
+                    }
+                    continue;
+                }
+                if depth == max_depth {
+                    continue;
+                }
+                let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+                let mut children: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                    .filter(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        !name.starts_with('.') && !SCAN_SKIP_DIRS.contains(&name.as_str())
+                    })
+                    .map(|e| e.path())
+                    .collect();
+                children.sort();
+                next.extend(children);
             }
+            frontier = next;
         }
     }
+    found
+}
+
+/// Directories the fallback scan searches below
+fn project_search_bases(home: &std::path::Path) -> Vec<PathBuf> {
+    vec![
+        home.join("Desktop"),
+        home.join("Projects"),
+        home.join("dev"),
+        home.to_path_buf(),
+    ]
+}
+
+/// Places a SYSTEM checkout usually lives, checked before any scan
+fn common_project_paths(home: &std::path::Path) -> Vec<PathBuf> {
+    vec![
+        home.join("Desktop/cua"),
+        home.join("Desktop/system"),
+        home.join("Projects/system"),
+        home.join("code/system"),
+    ]
+}
+
+/// Every SYSTEM checkout found under the usual base directories
+pub fn discover_project_roots(config: Option<&Config>) -> Vec<PathBuf> {
+    let Ok(home) = crate::config::home_dir() else { return Vec::new() };
+    let depth = config.map(|c| c.project_search_depth()).unwrap_or(crate::config::DEFAULT_PROJECT_SEARCH_DEPTH);
+    scan_for_projects(&project_search_bases(&home), depth)
+}
+
+/// Show a path relative to home as `~/...`, which is how users think of these locations
+fn display_path(path: &std::path::Path, home: Option<&std::path::Path>) -> String {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Explain a failed search with no projectRoot configured: every path probed, and where the scan looked
+fn project_not_found_message(home: Option<&std::path::Path>, depth: usize) -> String {
+    let Some(home) = home else {
+        return "Could not find SYSTEM project: no projectRoot is configured, and the home directory is unknown so nothing else was searched".to_string();
+    };
     
-    if let Ok(home) = std::env::var("HOME") {
-        let common_paths = [
-            format!("{}/Desktop/cua", home),
-            format!("{}/Desktop/system", home),
-            format!("{}/Projects/system", home),
-            format!("{}/code/system", home),
-        ];
-        
-        for p in common_paths {
-            let path = PathBuf::from(&p);
+    let list = |paths: Vec<PathBuf>| {
+        paths.iter().map(|p| display_path(p, Some(home))).collect::<Vec<_>>().join(", ")
+    };
+    format!(
+        "Could not find SYSTEM project: no projectRoot is configured; checked {}; scanned {} up to {} levels deep - none contained cloudflare-agent",
+        list(common_project_paths(home)),
+        list(project_search_bases(home)),
+        depth
+    )
+}
+
+pub fn find_project_root(config: Option<&Config>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    find_project_root_with(config, true)
+}
+
+/// Find the project root; with `persist`, a root the scan turns up is saved so the scan only runs once
+/// Dry runs pass `persist: false` so looking never writes config.json
+fn find_project_root_with(config: Option<&Config>, persist: bool) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    // A configured root is the user's choice: if it doesn't hold the project right now (say, an
+    // unmounted volume), say so rather than quietly running, or saving, some other copy
+    if let Some(root) = config.and_then(|c| c.project_root.as_deref()) {
+        return Ok(PathBuf::from(crate::config::normalize_project_root(root)?));
+    }
+    
+    let home = crate::config::home_dir().ok();
+    if let Some(ref home) = home {
+        for path in common_project_paths(home) {
             if path.join("cloudflare-agent").exists() {
                 return Ok(path);
             }
         }
     }
     
-    Err("Could not find SYSTEM project".into())
-}
-
-pub async fn start_local_server(api_secret: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let config = crate::config::load_config().ok();
-    let project_root = find_project_root(config.as_ref())?;
-    let agent_dir = project_root.join("cloudflare-agent");
-    
-    // Always write .dev.vars with API key and the generated API secret
-    if let Some(cfg) = &config {
-        if let Some(ref api_key) = cfg.anthropic_key {
-            // Use the provided api_secret for both bridge auth and API secret
-            let dev_vars = format!(
-                "ANTHROPIC_API_KEY={}\nBRIDGE_URL=http://localhost:3000\nBRIDGE_AUTH_TOKEN={}\nAPI_SECRET={}\n",
-                api_key, api_secret, api_secret
-            );
-            std::fs::write(agent_dir.join(".dev.vars"), dev_vars)?;
-            
-            // Also write the bridge config so the bridge server uses the same token
-            let bridge_config = serde_json::json!({
-                "authToken": api_secret
-            });
-            std::fs::write(
-                project_root.join("bridge.config.json"),
-                serde_json::to_string_pretty(&bridge_config)?
-            )?;
+    // Fall back to a bounded scan, and remember what it finds so it only runs once
+    // Only reached with no projectRoot configured, so this never replaces the user's choice
+    if let Some(path) = discover_project_roots(config).into_iter().next() {
+        if let (true, Ok(mut saved)) = (persist, crate::config::load_config()) {
+            saved.project_root = crate::config::normalize_project_root(&path.to_string_lossy()).ok();
+            let _ = crate::config::save_config(&saved);
         }
+        return Ok(path);
     }
     
-    // Kill ANY process on ports 3000 and 8787 (in case of orphaned processes from crashed app)
-    let _ = Command::new("sh")
-        .args(["-c", "lsof -ti:3000 | xargs kill -9 2>/dev/null; lsof -ti:8787 | xargs kill -9 2>/dev/null"])
-        .output();
-    
-    // Also kill by process name for good measure
-    let _ = Command::new("pkill").args(["-9", "-f", "wrangler dev"]).output();
-    let _ = Command::new("pkill").args(["-9", "-f", "http-server.js"]).output();
-    
-    // Small delay to let ports free up
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let depth = config.map(|c| c.project_search_depth()).unwrap_or(crate::config::DEFAULT_PROJECT_SEARCH_DEPTH);
+    Err(project_not_found_message(home.as_deref(), depth).into())
+}
+
+/// Oldest project version this app knows how to run
+const MIN_PROJECT_VERSION: &str = "1.0.0";
+
+/// Files every compatible project has, relative to its root
+const PROJECT_MARKERS: &[&str] = &[
+    "package.json",
+    "src/bridge/http-server.ts",
+    "cloudflare-agent/wrangler.jsonc",
+    "cloudflare-agent/src/server.ts",
+];
+
+/// Make sure a found project has the layout and version this app expects
+/// Tells "found it, but it's the wrong shape" apart from "not found"
+pub fn check_project_compatibility(project_root: &std::path::Path) -> Result<(), AppError> {
+    let missing: Vec<String> = PROJECT_MARKERS.iter()
+        .filter(|marker| !project_root.join(marker).exists())
+        .map(|marker| marker.to_string())
+        .collect();
+    let found_version = std::fs::read_to_string(project_root.join("package.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|json| json.get("version")?.as_str().map(String::from));
     
-    // Clear our tracked processes too
-    {
-        let mut guard = LOCAL_SERVER_PROCESS.lock().unwrap();
-        if let Some(ref mut child) = *guard {
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-        *guard = None;
+    let too_old = found_version.as_deref().is_none_or(|v| !version_at_least(v, MIN_PROJECT_VERSION));
+    if missing.is_empty() && !too_old {
+        return Ok(());
     }
-    {
-        let mut guard = BRIDGE_PROCESS.lock().unwrap();
-        if let Some(ref mut child) = *guard {
-            let _ = child.kill();
-            let _ = child.wait();
+    Err(AppError::ProjectIncompatible { found_version, required: MIN_PROJECT_VERSION.to_string(), missing })
+}
+
+/// Compare dotted numeric versions; anything unparseable counts as zero
+fn version_at_least(version: &str, required: &str) -> bool {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v').split('.').map(|p| p.parse().unwrap_or(0)).collect()
+    };
+    let (version, required) = (parts(version), parts(required));
+    for i in 0..version.len().max(required.len()) {
+        let (have, need) = (version.get(i).copied().unwrap_or(0), required.get(i).copied().unwrap_or(0));
+        if have != need {
+            return have > need;
         }
-        *guard = None;
     }
-    
-    // Start wrangler dev
-    let child = create_command("npx")
-        .args(["wrangler", "dev", "--port", "8787"])
-        .current_dir(&agent_dir)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()?;
-    
-    *LOCAL_SERVER_PROCESS.lock().unwrap() = Some(child);
-    
-    // Start bridge
-    start_bridge(&project_root).await?;
-    
-    // Wait for server to be ready
-    tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
-    
-    Ok(())
+    true
 }
 
-async fn start_bridge(project_root: &PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let child = create_command("node")
-        .arg("dist/bridge/http-server.js")
-        .current_dir(project_root)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()?;
-    
-    *BRIDGE_PROCESS.lock().unwrap() = Some(child);
-    Ok(())
+/// Address to connect to for a server bound to `bind`; a wildcard bind is reached over loopback
+fn reachable_host(bind: IpAddr) -> IpAddr {
+    match bind {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    }
 }
 
-pub async fn start_tunnel_and_get_url() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    // Check if already running
-    {
-        let mut guard = TUNNEL_PROCESS.lock().unwrap();
-        if let Some(ref mut child) = *guard {
-            if child.try_wait()?.is_none() {
-                return Err("Tunnel already running".into());
-            }
-        }
+/// URL this machine uses to reach a server bound to `bind` on `port`
+pub fn local_url(bind: IpAddr, port: u16) -> String {
+    match reachable_host(bind) {
+        host if host.is_loopback() && host.is_ipv4() => format!("http://localhost:{}", port),
+        host => format!("http://{}", SocketAddr::new(host, port)),
     }
-    
-    // Start cloudflared and capture stderr to get URL
-    let mut child = create_command("cloudflared")
-        .args(["tunnel", "--url", "http://localhost:8787"])
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .spawn()?;
-    
-    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
-    
-    // Read URL in a separate thread so we don't block
-    let (tx, rx) = std::sync::mpsc::channel::<String>();
-    
-    thread::spawn(move || {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines().flatten() {
-            // Look for the tunnel URL
-            if line.contains("trycloudflare.com") {
-                let trimmed = line.trim().trim_matches('|').trim();
-                if trimmed.starts_with("https://") {
-                    let _ = tx.send(trimmed.to_string());
-                    break;
-                }
-                // Try to find URL in the line
-                for word in line.split_whitespace() {
-                    let clean = word.trim_matches('|');
-                    if clean.starts_with("https://") && clean.contains("trycloudflare.com") {
-                        let _ = tx.send(clean.to_string());
-                        break;
-                    }
-                }
-            }
+}
+
+/// A node install found under a version manager or the usual system locations
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCandidate {
+    pub node: PathBuf,
+    /// npx next to this node, if there is one
+    pub npx: Option<PathBuf>,
+    /// Output of `node --version`, or None if it wouldn't run
+    pub version: Option<String>,
+}
+
+/// Directories whose children are per-version node installs, with the path from each child to its bin dir
+const VERSION_MANAGER_DIRS: &[(&str, &str)] = &[
+    (".nvm/versions/node", "bin"),
+    (".volta/tools/image/node", "bin"),
+    (".asdf/installs/nodejs", "bin"),
+    (".local/share/fnm/node-versions", "installation/bin"),
+    ("Library/Application Support/fnm/node-versions", "installation/bin"),
+];
+
+/// Every node binary under `home`'s version managers, plus Homebrew and /usr/local
+fn node_candidates(home: &std::path::Path) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for (dir, bin) in VERSION_MANAGER_DIRS {
+        let Ok(entries) = std::fs::read_dir(home.join(dir)) else {
+            continue;
+        };
+        let mut versions: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path().join(bin).join("node"))
+            .filter(|node| node.is_file())
+            .collect();
+        versions.sort();
+        candidates.extend(versions);
+    }
+    for system in ["/opt/homebrew/bin/node", "/usr/local/bin/node"] {
+        let node = PathBuf::from(system);
+        if node.is_file() {
+            candidates.push(node);
         }
-        // Keep draining stderr so the pipe doesn't block cloudflared
-        // This thread will exit when cloudflared exits
-    });
-    
-    // Store the child process
-    *TUNNEL_PROCESS.lock().unwrap() = Some(child);
-    
-    // Wait for URL with timeout
-    let url = rx.recv_timeout(std::time::Duration::from_secs(30))
-        .map_err(|_| "Timeout waiting for tunnel URL")?;
-    
-    Ok(url)
+    }
+    candidates
 }
 
-pub async fn stop_all() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    if let Some(mut child) = LOCAL_SERVER_PROCESS.lock().unwrap().take() {
-        let _ = child.kill();
+/// Node installs the user could pin with `nodeBin`/`npxBin`
+pub fn detect_node_versions() -> Vec<NodeCandidate> {
+    let Ok(home) = crate::config::home_dir() else {
+        return Vec::new();
+    };
+    node_candidates(&home)
+        .into_iter()
+        .map(|node| {
+            let npx = node.with_file_name("npx");
+            let version = Command::new(&node)
+                .arg("--version")
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+            NodeCandidate { npx: npx.exists().then_some(npx), node, version }
+        })
+        .collect()
+}
+
+/// Check whether something is accepting connections on a port of the bound interface
+fn port_is_listening(bind: IpAddr, port: u16) -> bool {
+    let addr = SocketAddr::new(reachable_host(bind), port);
+    TcpStream::connect_timeout(&addr, Duration::from_millis(500)).is_ok()
+}
+
+/// Check whether a local port can be bound, i.e. nothing else holds it
+pub fn port_is_free(port: u16) -> bool {
+    TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).is_ok()
+}
+
+/// Check an API key against the Anthropic API, or the configured gateway in front of it
+/// The key goes to curl on stdin so it never shows up in `ps`
+pub fn test_api_key(api_key: &str, base_url: &str, config: &Config) -> Result<(), String> {
+    let mut child = create_command("curl", config)
+        .args(["-s", "-o", "/dev/null", "-w", "%{http_code}", "--max-time", "10"])
+        .args(["-H", "@-", "-H", "anthropic-version: 2023-06-01"])
+        .arg(format!("{}/v1/models", base_url))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "x-api-key: {}", api_key.trim());
     }
-    if let Some(mut child) = TUNNEL_PROCESS.lock().unwrap().take() {
-        let _ = child.kill();
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let code: u16 = String::from_utf8_lossy(&output.stdout).trim().parse().unwrap_or(0);
+    match code {
+        200..=299 => Ok(()),
+        401 | 403 => Err("The API key was rejected - copy it again from console.anthropic.com".to_string()),
+        0 => Err(format!("Couldn't reach {}", crate::config::redact_url_credentials(base_url))),
+        code => Err(format!("Unexpected response from {} (HTTP {})", crate::config::redact_url_credentials(base_url), code)),
     }
-    if let Some(mut child) = BRIDGE_PROCESS.lock().unwrap().take() {
-        let _ = child.kill();
+}
+
+/// Whether a port the local server needs is free, and what's holding it if not
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortCheck {
+    pub port: u16,
+    pub free: bool,
+    pub holder_pid: Option<u32>,
+    /// Command line of the holder, so the user knows what to quit
+    pub holder: Option<String>,
+}
+
+/// PID of whatever is listening on a local TCP port, if lsof can tell us
+fn port_holder_pid(port: u16) -> Option<u32> {
+    let output = Command::new("lsof")
+        .args(["-nP", "-t", &format!("-iTCP:{}", port), "-sTCP:LISTEN"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).lines().next()?.trim().parse().ok()
+}
+
+fn check_port(port: u16) -> PortCheck {
+    if port_is_free(port) {
+        return PortCheck { port, free: true, holder_pid: None, holder: None };
+    }
+    let holder_pid = port_holder_pid(port);
+    PortCheck { port, free: false, holder_pid, holder: holder_pid.and_then(process_command) }
+}
+
+/// Process group of `pid`, if ps can tell us
+fn process_group_of(pid: u32) -> Option<u32> {
+    let output = Command::new("ps").args(["-o", "pgid=", "-p", &pid.to_string()]).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Whether `pid` belongs to a process we manage: the process itself, or a descendant in its group
+/// Managed processes lead their own group, so the node or workerd actually holding a port shares the tracked pid as its pgid
+fn is_managed(pid: u32, pgid: Option<u32>, managed: &[u32]) -> bool {
+    managed.contains(&pid) || pgid.is_some_and(|pgid| managed.contains(&pgid))
+}
+
+/// Whether `pid` is one of ours, e.g. the holder of a port a preflight found taken
+pub fn is_managed_pid(pid: u32) -> bool {
+    is_managed(pid, process_group_of(pid), &PROCESSES.pids())
+}
+
+/// Ports `start_local_server` needs in the configured mode
+fn required_ports(config: &Config) -> Vec<u16> {
+    if config.is_local_only() {
+        vec![config.bridge_port()]
+    } else {
+        vec![config.bridge_port(), config.worker_port()]
+    }
+}
+
+/// Probe each port the local server needs, naming the holder of any that are taken
+pub fn check_ports() -> Vec<PortCheck> {
+    let config = crate::config::load_config().unwrap_or_default();
+    required_ports(&config).into_iter().map(check_port).collect()
+}
+
+/// A subprocess we would spawn: program, arguments, and working directory
+/// Paths only ever travel in `cwd` and `args`, never through a shell, so spaces and non-ASCII survive
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandPlan {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    /// Variables set on top of the inherited environment
+    pub env: Vec<(String, String)>,
+}
+
+impl CommandPlan {
+    /// Build the command in its own process group so `stop` can take down its descendants too
+    fn command(&self, config: &Config) -> Command {
+        let mut cmd = create_command(&self.program, config);
+        cmd.args(&self.args).current_dir(&self.cwd).envs(self.env.iter().cloned()).process_group(0);
+        cmd
+    }
+
+    /// Program and arguments as one line, for display
+    fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Everything `start_local_server` would do, resolved up front
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchPlan {
+    pub project_root: PathBuf,
+    /// Contents of cloudflare-agent/.dev.vars, or None when no API key is saved
+    pub dev_vars: Option<String>,
+    /// None in local-only mode, where the bridge runs on its own
+    pub wrangler: Option<CommandPlan>,
+    pub bridge: CommandPlan,
+    /// PATH every subprocess inherits
+    pub path: String,
+}
+
+fn dev_vars_content(api_key: &str, bridge_port: u16, api_secret: &str) -> String {
+    // Use the provided api_secret for both bridge auth and API secret
+    format!(
+        "ANTHROPIC_API_KEY={}\nBRIDGE_URL=http://localhost:{}\nBRIDGE_AUTH_TOKEN={}\nAPI_SECRET={}\n",
+        api_key, bridge_port, api_secret, api_secret
+    )
+}
+
+/// `wrangler dev` serving on `bind_host:worker_port`
+fn wrangler_dev_plan(agent_dir: PathBuf, bind_host: IpAddr, worker_port: u16) -> CommandPlan {
+    CommandPlan {
+        program: "npx".to_string(),
+        args: vec![
+            "wrangler".into(),
+            "dev".into(),
+            "--ip".into(),
+            bind_host.to_string(),
+            "--port".into(),
+            worker_port.to_string(),
+        ],
+        cwd: agent_dir,
+        env: Vec::new(),
+    }
+}
+
+/// The node bridge, or the override from `bridgeCommand`/`bridgeArgs` when one is set
+/// Either way the bridge is told its port through PORT, which http-server.js listens on
+fn bridge_plan(project_root: &std::path::Path, config: Option<&Config>) -> Result<CommandPlan, Box<dyn std::error::Error + Send + Sync>> {
+    let cwd = project_root.to_path_buf();
+    let bridge_port = config.map(|c| c.bridge_port()).unwrap_or(crate::config::DEFAULT_BRIDGE_PORT);
+    let env = vec![("PORT".to_string(), bridge_port.to_string())];
+    match config.and_then(|c| c.bridge_command.as_deref()) {
+        Some(program) if program.trim().is_empty() => {
+            Err("bridgeCommand is set but empty - remove it or name the program that runs the bridge".into())
+        }
+        Some(program) => Ok(CommandPlan {
+            program: program.trim().to_string(),
+            args: config.and_then(|c| c.bridge_args.clone()).unwrap_or_default(),
+            cwd,
+            env,
+        }),
+        None => Ok(CommandPlan {
+            program: "node".to_string(),
+            args: vec!["dist/bridge/http-server.js".into()],
+            cwd,
+            env,
+        }),
+    }
+}
+
+fn build_plan(config: Option<&Config>, dev_vars: impl Fn(&str, u16) -> String) -> Result<LaunchPlan, Box<dyn std::error::Error + Send + Sync>> {
+    build_plan_with(config, true, dev_vars)
+}
+
+/// `build_plan`, saving a scanned-for project root only when `persist` is set
+fn build_plan_with(
+    config: Option<&Config>,
+    persist: bool,
+    dev_vars: impl Fn(&str, u16) -> String,
+) -> Result<LaunchPlan, Box<dyn std::error::Error + Send + Sync>> {
+    let project_root = find_project_root_with(config, persist)?;
+    check_project_compatibility(&project_root)?;
+    let worker_port = config.map(|c| c.worker_port()).unwrap_or(crate::config::DEFAULT_WORKER_PORT);
+    let bridge_port = config.map(|c| c.bridge_port()).unwrap_or(crate::config::DEFAULT_BRIDGE_PORT);
+    let local_only = config.is_some_and(|c| c.is_local_only());
+    if !local_only && bridge_port == worker_port {
+        return Err(format!("bridgePort and workerPort are both {} - give the bridge its own port", bridge_port).into());
+    }
+    
+    // Catch a bad pinned node/npx here rather than as a spawn failure later
+    for program in ["node", "npx"] {
+        if let Some(bin) = config.and_then(|c| pinned_binary(program, c)) {
+            crate::config::check_executable(&bin)?;
+        }
+    }
+    let bind_host = config.map(|c| c.bind_host()).transpose()?.unwrap_or(crate::config::DEFAULT_BIND_HOST);
+    let base_url = config.map(|c| c.anthropic_base_url()).transpose()?.flatten();
+    
+    Ok(LaunchPlan {
+        dev_vars: config.and_then(|c| c.anthropic_key.as_deref()).map(|api_key| {
+            let mut vars = dev_vars(api_key, bridge_port);
+            if let Some(ref url) = base_url {
+                vars.push_str(&format!("ANTHROPIC_BASE_URL={}\n", url));
+            }
+            vars
+        }),
+        wrangler: (!local_only)
+            .then(|| wrangler_dev_plan(project_root.join("cloudflare-agent"), bind_host, worker_port)),
+        bridge: bridge_plan(&project_root, config)?,
+        path: get_path_env(config.unwrap_or(&Config::default())),
+        project_root,
+    })
+}
+
+/// Resolve what `start_local_server` would run without spawning or writing anything
+/// Secrets in the returned `.dev.vars` are redacted
+pub fn plan_start() -> Result<LaunchPlan, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().ok();
+    let mut plan = build_plan_with(config.as_ref(), false, |api_key, bridge_port| {
+        dev_vars_content(&crate::config::redact(api_key), bridge_port, "<generated on start>")
+    })?;
+    plan.dev_vars = plan.dev_vars.map(|vars| {
+        vars.lines()
+            .map(|line| match line.strip_prefix("ANTHROPIC_BASE_URL=") {
+                Some(url) => format!("ANTHROPIC_BASE_URL={}\n", crate::config::redact_url_credentials(url)),
+                None => format!("{}\n", line),
+            })
+            .collect()
+    });
+    Ok(plan)
+}
+
+/// One process the app would launch, as it would launch it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchCommand {
+    /// "wrangler", "bridge", or "tunnel"
+    pub name: String,
+    /// Absolute path when the program can be found, otherwise the bare name
+    pub program: String,
+    pub args: Vec<String>,
+    /// None for processes that run from wherever the app was started
+    pub cwd: Option<PathBuf>,
+    /// Variables set on top of the inherited environment, secrets redacted
+    pub env: Vec<(String, String)>,
+    /// The same thing as one line to paste into a terminal
+    pub shell: String,
+}
+
+/// Environment variable names whose values are never shown
+const SECRET_ENV_MARKERS: &[&str] = &["KEY", "SECRET", "TOKEN", "PASSWORD", "AUTH"];
+
+/// Quote `arg` for sh, leaving plain words alone
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn launch_command(name: &str, program: &str, args: Vec<String>, cwd: Option<PathBuf>, env: Vec<(String, String)>, config: &Config) -> LaunchCommand {
+    let program = resolve_binary(program, config).map(|p| p.to_string_lossy().to_string()).unwrap_or_else(|| program.to_string());
+    let env: Vec<(String, String)> = env
+        .into_iter()
+        .map(|(key, value)| {
+            let secret = SECRET_ENV_MARKERS.iter().any(|marker| key.to_uppercase().contains(marker));
+            let value = if secret { crate::config::redact(&value) } else { value };
+            (key, value)
+        })
+        .collect();
+    
+    let mut words: Vec<String> = env.iter().map(|(key, value)| format!("{}={}", key, shell_quote(value))).collect();
+    words.push(shell_quote(&program));
+    words.extend(args.iter().map(|arg| shell_quote(arg)));
+    let shell = match cwd {
+        Some(ref dir) => format!("cd {} && {}", shell_quote(&dir.to_string_lossy()), words.join(" ")),
+        None => words.join(" "),
+    };
+    LaunchCommand { name: name.to_string(), program, args, cwd, env, shell }
+}
+
+/// What wrangler, the bridge, and the tunnel would each be run as under `config`
+fn launch_commands_for(config: &Config) -> Result<Vec<LaunchCommand>, Box<dyn std::error::Error + Send + Sync>> {
+    let plan = build_plan_with(Some(config), false, |_, _| String::new())?;
+    let with_path = |env: &[(String, String)]| {
+        let mut env = env.to_vec();
+        env.insert(0, ("PATH".to_string(), plan.path.clone()));
+        env
+    };
+    
+    let mut commands = Vec::new();
+    if let Some(ref wrangler) = plan.wrangler {
+        commands.push(launch_command("wrangler", &wrangler.program, wrangler.args.clone(), Some(wrangler.cwd.clone()), with_path(&wrangler.env), config));
+    }
+    let bridge = &plan.bridge;
+    commands.push(launch_command("bridge", &bridge.program, bridge.args.clone(), Some(bridge.cwd.clone()), with_path(&bridge.env), config));
+    
+    let provider = tunnel_provider(config)?;
+    let args = provider.args(config.bind_host()?, tunnel_target_port(config));
+    commands.push(launch_command("tunnel", provider.program(), args, None, with_path(&[]), config));
+    Ok(commands)
+}
+
+/// The exact command lines the app would run for the current config, to rerun by hand
+/// Read-only: nothing is spawned or written
+pub fn launch_commands() -> Result<Vec<LaunchCommand>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    launch_commands_for(&config)
+}
+
+/// Write the `.dev.vars` and `bridge.config.json` that carry the session's secrets
+fn write_project_secrets(plan: &LaunchPlan, api_secret: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Always write .dev.vars with API key and the generated API secret
+    if let Some(ref dev_vars) = plan.dev_vars {
+        if let Some(ref wrangler) = plan.wrangler {
+            crate::config::write_file(&wrangler.cwd.join(".dev.vars"), dev_vars)?;
+        }
+        
+        // Also write the bridge config so the bridge server uses the same token
+        let bridge_config = serde_json::json!({
+            "authToken": api_secret
+        });
+        crate::config::write_file(
+            &plan.project_root.join("bridge.config.json"),
+            serde_json::to_string_pretty(&bridge_config)?
+        )?;
+    }
+    Ok(())
+}
+
+/// Delete the `.dev.vars` and `bridge.config.json` carrying secrets from the project
+/// Returns the files that were removed
+pub fn clear_project_secrets() -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().ok();
+    let project_root = find_project_root(config.as_ref())?;
+    let mut removed = Vec::new();
+    for path in project_secret_files(&project_root) {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// The files `start_local_server` writes secrets into
+fn project_secret_files(project_root: &std::path::Path) -> [PathBuf; 2] {
+    [project_root.join("cloudflare-agent").join(".dev.vars"), project_root.join("bridge.config.json")]
+}
+
+/// A project file that carries secrets, as found on disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretFileReport {
+    pub path: PathBuf,
+    pub exists: bool,
+    pub modified: Option<String>,
+    /// Whether the file's token is the running session's; None when nothing is running or the file has no token
+    pub matches_session: Option<bool>,
+    /// Whether git ignores the file; None outside a git checkout
+    pub git_ignored: Option<bool>,
+    /// The file's settings with their values redacted
+    pub entries: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Report on the secret-bearing files in the project: age, staleness, and whether git would commit them
+pub fn inspect_project_secrets(session_secret: Option<&str>) -> Result<Vec<SecretFileReport>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let project_root = find_project_root(Some(&config))?;
+    Ok(project_secret_files(&project_root)
+        .into_iter()
+        .map(|path| inspect_secret_file(&project_root, path, session_secret, &config))
+        .collect())
+}
+
+fn inspect_secret_file(project_root: &std::path::Path, path: PathBuf, session_secret: Option<&str>, config: &Config) -> SecretFileReport {
+    let contents = std::fs::read_to_string(&path).ok();
+    let token = contents.as_deref().and_then(|c| secret_file_token(&path, c));
+    let matches_session = session_secret.zip(token).map(|(session, token)| session == token);
+    let git_ignored = git_ignores(project_root, &path, config);
+    
+    let mut warnings = Vec::new();
+    if contents.is_some() && git_ignored == Some(false) {
+        warnings.push("Not ignored by git - add it to .gitignore so the secrets in it aren't committed".to_string());
+    }
+    if matches_session == Some(false) {
+        warnings.push("Stale - its token isn't the running session's; restart or clear it".to_string());
+    }
+    
+    SecretFileReport {
+        exists: contents.is_some(),
+        modified: std::fs::metadata(&path).and_then(|m| m.modified()).ok().map(format_timestamp),
+        matches_session,
+        git_ignored,
+        entries: contents.as_deref().map(|c| redacted_entries(&path, c)).unwrap_or_default(),
+        warnings,
+        path,
+    }
+}
+
+/// The session token a secrets file carries: `API_SECRET` in `.dev.vars`, `authToken` in the bridge config
+fn secret_file_token(path: &std::path::Path, contents: &str) -> Option<String> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let json: serde_json::Value = serde_json::from_str(contents).ok()?;
+        return json.get("authToken")?.as_str().map(String::from);
+    }
+    contents.lines().find_map(|line| line.trim().strip_prefix("API_SECRET=")).map(|v| v.trim().to_string())
+}
+
+/// `KEY=value` pairs from a secrets file with every value redacted
+fn redacted_entries(path: &std::path::Path, contents: &str) -> Vec<String> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let Ok(serde_json::Value::Object(map)) = serde_json::from_str::<serde_json::Value>(contents) else {
+            return Vec::new();
+        };
+        return map.iter()
+            .map(|(key, value)| format!("{}={}", key, crate::config::redact(value.as_str().unwrap_or(&value.to_string()))))
+            .collect();
+    }
+    contents.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| format!("{}={}", key.trim(), crate::config::redact(value.trim())))
+        .collect()
+}
+
+/// Ask git whether it ignores `path`; None when git isn't there or this isn't a checkout
+fn git_ignores(project_root: &std::path::Path, path: &std::path::Path, config: &Config) -> Option<bool> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_root)
+        .args(["check-ignore", "-q"])
+        .arg(path)
+        .env("PATH", get_path_env(config))
+        .output()
+        .ok()?;
+    match output.status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// Rewrite `.dev.vars` and `bridge.config.json` for a new secret without restarting anything
+pub fn rewrite_project_secrets(api_secret: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().ok();
+    let plan = build_plan(config.as_ref(), |api_key, bridge_port| dev_vars_content(api_key, bridge_port, api_secret))?;
+    write_project_secrets(&plan, api_secret)
+}
+
+/// Rewrite `.dev.vars` from the current config for the running session, which wrangler dev picks up live
+/// Returns the contents written, with secrets redacted
+pub fn sync_dev_vars(api_secret: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    sync_dev_vars_with(&config, api_secret)
+}
+
+fn sync_dev_vars_with(config: &Config, api_secret: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if config.anthropic_key.as_deref().is_none_or(|key| key.trim().is_empty()) {
+        return Err("No Anthropic API key saved - enter one before syncing .dev.vars".into());
+    }
+    let plan = build_plan(Some(config), |api_key, bridge_port| dev_vars_content(api_key, bridge_port, api_secret))?;
+    if plan.wrangler.is_none() {
+        return Err("Local-only mode doesn't run the worker, so there's no .dev.vars to sync".into());
+    }
+    write_project_secrets(&plan, api_secret)?;
+    Ok(redact_dev_vars(plan.dev_vars.as_deref().unwrap_or_default()))
+}
+
+/// `.dev.vars` contents with the key, secrets and gateway credentials masked
+fn redact_dev_vars(contents: &str) -> String {
+    contents
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name @ ("ANTHROPIC_API_KEY" | "BRIDGE_AUTH_TOKEN" | "API_SECRET"), value)) => {
+                format!("{}={}\n", name, crate::config::redact(value))
+            }
+            Some(("ANTHROPIC_BASE_URL", url)) => format!("ANTHROPIC_BASE_URL={}\n", crate::config::redact_url_credentials(url)),
+            _ => format!("{}\n", line),
+        })
+        .collect()
+}
+
+/// Make sure node has a compiled entry point to run, rather than letting it exit silently
+fn ensure_bridge_built(bridge: &CommandPlan) -> Result<(), AppError> {
+    let entry = bridge.cwd.join(bridge.args.first().map(String::as_str).unwrap_or_default());
+    if entry.is_file() {
+        Ok(())
+    } else {
+        Err(AppError::BridgeNotBuilt { entry })
+    }
+}
+
+/// Result of running the project's build step
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildOutput {
+    pub success: bool,
+    pub output: String,
+}
+
+/// Compile the project's TypeScript (`npm run build`) and capture what it printed
+pub fn build_project() -> Result<BuildOutput, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let project_root = find_project_root(Some(&config))?;
+    
+    let output = create_command("npm", &config)
+        .args(["run", "build"])
+        .current_dir(&project_root)
+        .output()?;
+    
+    Ok(BuildOutput {
+        success: output.status.success(),
+        output: format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    })
+}
+
+/// Report the outcome of a step through `progress` and pass the result on
+fn finish_step<T, E>(progress: Progress, stage: &str, result: Result<T, E>) -> Result<T, E> {
+    progress(stage, if result.is_ok() { "done" } else { "failed" });
+    result
+}
+
+/// Starts in progress, so a second start can't slip in before the first has registered its process
+#[derive(Default)]
+struct StartFlags(Mutex<std::collections::HashSet<ProcessKind>>);
+
+impl StartFlags {
+    /// Mark `kind` as starting, or fail with `Busy` if it already is
+    fn claim(&self, kind: ProcessKind) -> Result<StartGuard<'_>, AppError> {
+        if !self.0.lock_or_recover().insert(kind) {
+            return Err(AppError::Busy);
+        }
+        Ok(StartGuard { flags: self, kind })
+    }
+    
+    /// Claim `kind` and refuse if `registry` already has it running
+    /// Checking after the claim means two racing starts can't both see it stopped; hold the guard until the new process is registered
+    fn claim_idle(&self, registry: &ProcessRegistry, kind: ProcessKind) -> Result<StartGuard<'_>, Box<dyn std::error::Error + Send + Sync>> {
+        let guard = self.claim(kind)?;
+        if registry.is_running(kind) {
+            return Err(format!("{} already running", kind.log_name()).into());
+        }
+        Ok(guard)
+    }
+}
+
+/// Clears its starting flag when the start finishes, fails, or is dropped mid-way
+struct StartGuard<'a> {
+    flags: &'a StartFlags,
+    kind: ProcessKind,
+}
+
+impl Drop for StartGuard<'_> {
+    fn drop(&mut self) {
+        self.flags.0.lock_or_recover().remove(&self.kind);
+    }
+}
+
+static STARTING: Lazy<StartFlags> = Lazy::new(StartFlags::default);
+
+/// Start the local processes for the configured mode:
+/// - default: wrangler dev (the worker) plus the node bridge behind it
+/// - local-only: just the node bridge, which the tunnel then points at directly
+pub async fn start_local_server(api_secret: &str, progress: Progress<'_>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // A second Start while this one is still spawning would otherwise launch a duplicate wrangler
+    let _starting = STARTING.claim(ProcessKind::LocalServer)?;
+    progress("find_root", "running");
+    // Read once, so the whole start runs on one version of the config even if it's edited meanwhile
+    let config = crate::config::load_config().unwrap_or_default();
+    let plan = finish_step(progress, "find_root", build_plan(Some(&config), |api_key, bridge_port| dev_vars_content(api_key, bridge_port, api_secret)).and_then(|plan| {
+        // A custom bridge command runs from source, so there's no build output to check
+        if config.bridge_command.is_none() {
+            ensure_bridge_built(&plan.bridge)?;
+        }
+        Ok(plan)
+    }))?;
+    let worker_port = config.worker_port();
+    let bind_host = config.bind_host()?;
+    let health_path = config.health_path();
+    let timing = BootTiming::for_config(Some(&config));
+    
+    // Anything beyond loopback is reachable by other machines, so it must be authenticated
+    if !bind_host.is_loopback() {
+        if api_secret.is_empty() {
+            return Err(format!("Refusing to bind to {} without an API secret", bind_host).into());
+        }
+        log("warn", "system", &format!("Local server bound to {} - it is reachable from other devices on the network", bind_host));
+    }
+    
+    progress("write_vars", "running");
+    finish_step(progress, "write_vars", write_project_secrets(&plan, api_secret))?;
+    
+    progress("free_ports", "running");
+    
+    // Only processes we can tell are ours are killed: the ones we track, and orphans of a crashed session
+    stop_async(ProcessKind::LocalServer).await;
+    stop_async(ProcessKind::Bridge).await;
+    let killed = tokio::task::spawn_blocking(kill_orphans).await.unwrap_or_default();
+    if !killed.is_empty() {
+        // Small delay to let ports free up
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    
+    // Whatever still holds a port isn't ours to kill, so name it rather than fail to bind later
+    let required = required_ports(&config);
+    let checks = tokio::task::spawn_blocking(move || required.into_iter().map(check_port).collect::<Vec<_>>())
+        .await
+        .unwrap_or_default();
+    if let Some(taken) = checks.into_iter().find(|check| !check.free) {
+        progress("free_ports", "failed");
+        return Err(AppError::PortInUse { port: taken.port, holder: taken.holder }.into());
+    }
+    progress("free_ports", "done");
+    
+    // Anything that fails from here on would otherwise leave a half-started server that the next
+    // start takes for a running one
+    let launched: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
+        // Start wrangler dev
+        let mut spawned = std::time::Instant::now();
+        if let Some(ref wrangler) = plan.wrangler {
+            progress("start_worker", "running");
+            finish_step(progress, "start_worker", spawn_worker(wrangler, &config))?;
+        }
+        
+        // Start bridge
+        progress("start_bridge", "running");
+        finish_step(progress, "start_bridge", start_bridge(&plan.bridge, &config).await)?;
+        
+        // In local-only mode there is no worker to wait for
+        let Some(ref wrangler) = plan.wrangler else {
+            return Ok(());
+        };
+        
+        // Wait for the worker to listen, respawning it if it dies while booting
+        progress("wait_ready", "running");
+        let mut failures = Vec::new();
+        for attempt in 0..=WORKER_BOOT_RETRIES {
+            if attempt > 0 {
+                progress("restart_worker", "running");
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                finish_step(progress, "restart_worker", spawn_worker(wrangler, &config))?;
+                spawned = std::time::Instant::now();
+            }
+            
+            match wait_for_worker(bind_host, worker_port, &health_path, api_secret, &timing, &config).await {
+                Boot::Ready => {
+                    progress("wait_ready", "done");
+                    record_worker_boot(spawned.elapsed());
+                    return Ok(());
+                }
+                Boot::TimedOut => {
+                    progress("wait_ready", "failed");
+                    return Err(format!(
+                        "wrangler didn't start listening on port {} within {}s",
+                        worker_port,
+                        timing.timeout.as_secs()
+                    ).into());
+                }
+                Boot::Died => {
+                    let output = log_tail(ProcessKind::LocalServer, 20).join("\n");
+                    // A broken project fails the same way every time, so don't bother retrying
+                    if is_config_error(&output) {
+                        progress("wait_ready", "failed");
+                        return Err(format!("wrangler failed to start: {}", output).into());
+                    }
+                    let reason = output.lines().last().unwrap_or("exited with no output").to_string();
+                    failures.push(format!("attempt {}: {}", attempt + 1, reason));
+                }
+            }
+        }
+        
+        progress("wait_ready", "failed");
+        Err(format!("wrangler kept exiting during startup ({})", failures.join("; ")).into())
+    }
+    .await;
+    if launched.is_err() {
+        stop_async(ProcessKind::LocalServer).await;
+        stop_async(ProcessKind::Bridge).await;
+    }
+    launched
+}
+
+/// How many times a worker that dies while booting is respawned
+const WORKER_BOOT_RETRIES: u32 = 2;
+
+/// How long to wait for the worker to accept connections before we know how fast it boots
+const WORKER_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Never wait longer than this for the worker, however slow past boots were
+const WORKER_READY_TIMEOUT_MAX: Duration = Duration::from_secs(90);
+
+/// Never give up sooner than this, however fast past boots were
+const WORKER_READY_TIMEOUT_MIN: Duration = Duration::from_secs(10);
+
+/// Weight of the newest boot in the rolling estimate, in percent
+const BOOT_ESTIMATE_WEIGHT: u64 = 30;
+
+/// How to poll for the worker, sized from how long it took to boot before
+#[derive(Debug, PartialEq)]
+struct BootTiming {
+    /// Don't bother polling before this much of the usual boot time has passed
+    first_check: Duration,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl BootTiming {
+    fn from_estimate(estimate_ms: Option<u64>) -> Self {
+        let Some(estimate_ms) = estimate_ms else {
+            return Self { first_check: Duration::ZERO, interval: Duration::from_millis(250), timeout: WORKER_READY_TIMEOUT };
+        };
+        Self {
+            first_check: Duration::from_millis(estimate_ms / 2).min(WORKER_READY_TIMEOUT_MIN),
+            interval: Duration::from_millis((estimate_ms / 10).clamp(100, 500)),
+            timeout: Duration::from_millis(estimate_ms.saturating_mul(3)).clamp(WORKER_READY_TIMEOUT_MIN, WORKER_READY_TIMEOUT_MAX),
+        }
+    }
+    
+    /// The adaptive timing, with whatever the user pinned in `timeouts` taking precedence
+    fn for_config(config: Option<&Config>) -> Self {
+        let mut timing = Self::from_estimate(config.and_then(|c| c.worker_boot_ms));
+        if let Some(timeout) = config.and_then(|c| c.readiness_timeout()) {
+            timing.timeout = timeout;
+            timing.first_check = timing.first_check.min(timeout / 2);
+        }
+        if let Some(interval) = config.and_then(|c| c.poll_interval()) {
+            timing.interval = interval;
+        }
+        timing
+    }
+}
+
+/// Fold a newly observed boot time into the rolling estimate
+fn update_boot_estimate(previous_ms: Option<u64>, observed_ms: u64) -> u64 {
+    // A boot that hit the hard limit says little about the next one
+    let observed_ms = observed_ms.min(WORKER_READY_TIMEOUT_MAX.as_millis() as u64);
+    match previous_ms {
+        Some(previous) => (previous * (100 - BOOT_ESTIMATE_WEIGHT) + observed_ms * BOOT_ESTIMATE_WEIGHT) / 100,
+        None => observed_ms,
+    }
+}
+
+/// Remember how long this boot took so the next start waits the right amount
+fn record_worker_boot(elapsed: Duration) {
+    if let Ok(mut config) = crate::config::load_config() {
+        let estimate = update_boot_estimate(config.worker_boot_ms, elapsed.as_millis() as u64);
+        config.worker_boot_ms = Some(estimate);
+        let _ = crate::config::save_config(&config);
+    }
+}
+
+/// wrangler output that means the project itself is broken, not a flaky boot
+const CONFIG_ERROR_SIGNATURES: &[&str] = &[
+    "Could not resolve",
+    "Missing entry-point",
+    "ParseError",
+    "Unexpected token",
+    "No such file or directory",
+    "wrangler.jsonc",
+];
+
+fn is_config_error(output: &str) -> bool {
+    CONFIG_ERROR_SIGNATURES.iter().any(|sig| output.contains(sig))
+}
+
+/// How waiting for a freshly spawned process to come up ended
+#[derive(Debug, PartialEq)]
+enum Boot {
+    Ready,
+    Died,
+    TimedOut,
+}
+
+/// Poll until the worker answers on its health path or the process goes away
+async fn wait_for_worker(bind_host: IpAddr, port: u16, health_path: &str, api_secret: &str, timing: &BootTiming, config: &Config) -> Boot {
+    let url = format!("{}{}", local_url(bind_host, port), health_path);
+    let started = std::time::Instant::now();
+    tokio::time::sleep(timing.first_check).await;
+    while started.elapsed() < timing.timeout {
+        // wrangler opens the port before the worker has compiled, so a bare connect isn't enough
+        // Each probe can take seconds, so they run on the blocking pool rather than a runtime worker
+        let (url, api_secret, config) = (url.clone(), api_secret.to_string(), config.clone());
+        let ready = tokio::task::spawn_blocking(move || {
+            port_is_listening(bind_host, port) && status_means_up(http_status(&url, Some(&api_secret), &config))
+        })
+        .await
+        .unwrap_or(false);
+        if ready {
+            return Boot::Ready;
+        }
+        if !is_running(ProcessKind::LocalServer) {
+            return Boot::Died;
+        }
+        tokio::time::sleep(timing.interval).await;
+    }
+    Boot::TimedOut
+}
+
+/// How long the node bridge gets to open its port, unless `timeouts.bridgeReadinessTimeoutSecs` says otherwise
+const BRIDGE_READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often the bridge port is checked while it boots, unless `timeouts.pollIntervalMs` says otherwise
+const BRIDGE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Poll until something accepts connections on `port`, giving up early once `alive` says the process exited
+async fn wait_for_port(host: IpAddr, port: u16, timeout: Duration, interval: Duration, alive: impl Fn() -> bool) -> Boot {
+    let started = std::time::Instant::now();
+    loop {
+        if tokio::task::spawn_blocking(move || port_is_listening(host, port)).await.unwrap_or(false) {
+            return Boot::Ready;
+        }
+        if !alive() {
+            return Boot::Died;
+        }
+        if started.elapsed() >= timeout {
+            return Boot::TimedOut;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Spawn wrangler dev with its output going to the log
+fn spawn_worker(wrangler: &CommandPlan, config: &Config) -> std::io::Result<()> {
+    let (stdout, stderr) = log_stdio(ProcessKind::LocalServer);
+    let child = spawn_resolved(wrangler.command(config).stdout(stdout).stderr(stderr), &wrangler.program, config)?;
+    
+    PROCESSES.register(ProcessKind::LocalServer, child, wrangler.command_line());
+    persist_runtime();
+    Ok(())
+}
+
+/// Spawn the bridge and wait for it to listen, so a crash on boot (bad dist, port clash) fails the start
+async fn start_bridge(bridge: &CommandPlan, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (stdout, stderr) = log_stdio(ProcessKind::Bridge);
+    let child = spawn_resolved(bridge.command(config).stdout(stdout).stderr(stderr), &bridge.program, config)?;
+    
+    PROCESSES.register(ProcessKind::Bridge, child, bridge.command_line());
+    persist_runtime();
+    
+    let port = config.bridge_port();
+    let timeout = config.bridge_readiness_timeout().unwrap_or(BRIDGE_READY_TIMEOUT);
+    let interval = config.poll_interval().unwrap_or(BRIDGE_POLL_INTERVAL);
+    let boot = wait_for_port(IpAddr::V4(Ipv4Addr::LOCALHOST), port, timeout, interval, || is_running(ProcessKind::Bridge)).await;
+    if boot == Boot::Ready {
+        return Ok(());
+    }
+    // start_local_server's rollback takes down this bridge along with the worker spawned before it
+    let output = log_tail(ProcessKind::Bridge, 20).join("\n");
+    Err(AppError::BridgeNotReady { port, exited: boot == Boot::Died, output }.into())
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement) from a line of terminal output
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        // CSI sequences run until a final byte in @..~; other escapes are a single character
+        if let Some('[') = chars.next() {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// The origin of a quick-tunnel URL, or None if `candidate` isn't a complete one
+/// Catches the trailing punctuation and cut-off hosts that colorized or truncated output leaves behind
+fn quick_tunnel_origin(candidate: &str) -> Option<String> {
+    let url = url::Url::parse(candidate).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let host = url.host_str()?;
+    let subdomain = host.strip_suffix(".trycloudflare.com")?;
+    let well_formed = !subdomain.is_empty()
+        && subdomain.split('.').all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    well_formed.then(|| format!("https://{}", host))
+}
+
+/// Pull a quick-tunnel URL out of a line of cloudflared output, if it has one
+/// The URL may be boxed in `|` characters or run straight on from other text
+fn extract_tunnel_url(line: &str) -> Option<String> {
+    let line = strip_ansi(line);
+    let mut rest = line.as_str();
+    while let Some(start) = rest.find("https://") {
+        let candidate: String = rest[start..]
+            .chars()
+            .take_while(|c| !c.is_whitespace() && *c != '|')
+            .collect();
+        if candidate.contains("trycloudflare.com") {
+            match quick_tunnel_origin(&candidate) {
+                Some(url) => return Some(url),
+                None => log("warn", "tunnel", &format!("Ignoring malformed tunnel URL: {}", candidate)),
+            }
+        }
+        rest = &rest[start + "https://".len()..];
+    }
+    None
+}
+
+/// Start the configured tunnel provider and wait for its URL
+/// The output reader keeps watching after that, calling `on_url_change(old, new)` if the tunnel
+/// reconnects under a different URL
+pub async fn start_tunnel_and_get_url(
+    on_url_change: impl Fn(String, String) + Send + 'static,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    // Check if already running, or about to be
+    let _starting = STARTING.claim_idle(&PROCESSES, ProcessKind::Tunnel)?;
+    
+    // Refuse to hand out a tunnel URL that would only serve connection errors
+    let config = crate::config::load_config().unwrap_or_default();
+    let target_port = tunnel_target_port(&config);
+    let bind_host = config.bind_host()?;
+    if !port_is_listening(bind_host, target_port) {
+        return Err(AppError::LocalServerNotRunning { port: target_port }.into());
+    }
+    
+    let provider: Arc<dyn TunnelProvider> = tunnel_provider(&config)?.into();
+    let mut log = open_log(ProcessKind::Tunnel);
+    let (child, output) = provider.start(bind_host, target_port, log.as_ref(), &config)?;
+    let command_line = provider.command_line(bind_host, target_port);
+    
+    // Read URL in a separate thread so we don't block
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<String, AppError>>();
+    
+    let watcher = provider.clone();
+    thread::spawn(move || {
+        let provider = watcher;
+        let reader = BufReader::new(output);
+        let mut tx = Some(tx);
+        let mut current: Option<String> = None;
+        for line in reader.lines().map_while(Result::ok) {
+            if let Some(ref mut file) = log {
+                write_output_line(file, ProcessKind::Tunnel.log_name(), &line);
+            }
+            
+            // Failures like rate limiting won't be fixed by waiting out the timeout
+            if let Some(err) = provider.failure(&line) {
+                if let Some(tx) = tx.take() {
+                    let _ = tx.send(Err(err));
+                }
+                continue;
+            }
+            
+            // Look for the tunnel URL, and for it changing after a reconnect
+            if let Some(url) = provider.url(&line) {
+                match current {
+                    None => {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(Ok(url.clone()));
+                        }
+                        current = Some(url);
+                    }
+                    Some(ref old) if *old != url => {
+                        on_url_change(old.clone(), url.clone());
+                        current = Some(url);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        // Keep draining the output so the pipe doesn't block the tunnel
+        // This thread will exit when the tunnel exits (or is stopped)
+    });
+    
+    // Store the child process
+    PROCESSES.register(ProcessKind::Tunnel, child, command_line);
+    persist_runtime();
+    
+    // Wait for URL with timeout, unless the user gives up first
+    let url_timeout = config.tunnel_url_timeout().unwrap_or(TUNNEL_URL_TIMEOUT);
+    let cancelled = TUNNEL_CANCEL.notified();
+    let result: Result<String, Box<dyn std::error::Error + Send + Sync>> = tokio::select! {
+        url = tokio::time::timeout(url_timeout, rx) => match url {
+            Ok(Ok(Ok(url))) => Ok(url),
+            Ok(Ok(Err(e))) => Err(e.into()),
+            Ok(Err(_)) => Err(format!("{} exited before reporting a tunnel URL", provider.program()).into()),
+            Err(_) => Err(AppError::TunnelTimeout { secs: url_timeout.as_secs() }.into()),
+        },
+        _ = cancelled => Err(AppError::Cancelled.into()),
+    };
+    
+    // Don't leave a tunnel behind that nobody is waiting on; stopping it ends the reader thread
+    if result.is_err() {
+        let _ = tokio::task::spawn_blocking(move || provider.stop()).await;
+    }
+    result
+}
+
+/// A program that exposes a local port publicly, run as the managed `Tunnel` process
+/// `start_tunnel_and_get_url` spawns it, watches its output for the URL, and registers it like any other process
+trait TunnelProvider: Send + Sync + 'static {
+    /// Program name, for messages
+    fn program(&self) -> &'static str;
+    /// Arguments that point the tunnel at `host:port`
+    fn args(&self, host: IpAddr, port: u16) -> Vec<String>;
+    /// Full command line, as shown in process status
+    fn command_line(&self, host: IpAddr, port: u16) -> String {
+        format!("{} {}", self.program(), self.args(host, port).join(" "))
+    }
+    /// Spawn the tunnel to `host:port` in its own process group, returning it and the output stream to watch
+    /// Output that isn't watched goes to `log`
+    fn start(&self, host: IpAddr, port: u16, log: Option<&File>, config: &Config) -> Result<(Child, Box<dyn std::io::Read + Send>), Box<dyn std::error::Error + Send + Sync>>;
+    /// The public URL a line of output announces, if it announces one
+    fn url(&self, line: &str) -> Option<String>;
+    /// A failure a line of output reports that waiting won't fix
+    fn failure(&self, _line: &str) -> Option<AppError> {
+        None
+    }
+    /// Tear the tunnel down
+    fn stop(&self) {
+        stop(ProcessKind::Tunnel);
+    }
+}
+
+/// The tunnel `tunnelProvider` selects; cloudflared unless told otherwise
+fn tunnel_provider(config: &Config) -> Result<Box<dyn TunnelProvider>, Box<dyn std::error::Error + Send + Sync>> {
+    match config.tunnel_provider.as_deref().map(str::trim) {
+        None | Some("cloudflared") => Ok(Box::new(Cloudflared {
+            named: config.cloudflared_config_path.as_deref().map(named_tunnel).transpose()?,
+        })),
+        Some("bore") => Ok(Box::new(Bore {
+            server: config.bore_server.clone().unwrap_or_else(|| DEFAULT_BORE_SERVER.to_string()),
+        })),
+        Some(other) => Err(format!("Unknown tunnelProvider \"{}\" - use \"cloudflared\" or \"bore\"", other).into()),
+    }
+}
+
+/// Two ways to run cloudflared:
+/// - quick tunnel (default): `tunnel --url`, with the random trycloudflare.com URL scraped from stderr
+/// - named tunnel (cloudflaredConfigPath): `tunnel --config <path> run`, with the URL fixed by the
+///   config's ingress hostname and ready once cloudflared registers a connection
+struct Cloudflared {
+    named: Option<(PathBuf, String)>,
+}
+
+impl TunnelProvider for Cloudflared {
+    fn program(&self) -> &'static str {
+        "cloudflared"
+    }
+    
+    fn args(&self, host: IpAddr, port: u16) -> Vec<String> {
+        match self.named {
+            Some((ref path, _)) => vec!["tunnel".into(), "--config".into(), path.to_string_lossy().to_string(), "run".into()],
+            None => vec!["tunnel".into(), "--url".into(), local_url(host, port)],
+        }
+    }
+    
+    fn start(&self, host: IpAddr, port: u16, log: Option<&File>, config: &Config) -> Result<(Child, Box<dyn std::io::Read + Send>), Box<dyn std::error::Error + Send + Sync>> {
+        // cloudflared reports on stderr; stdout goes straight to the log
+        let stdout = log
+            .and_then(|f| f.try_clone().ok())
+            .map(Stdio::from)
+            .unwrap_or_else(Stdio::null);
+        let mut child = spawn_resolved(
+            create_command("cloudflared", config)
+                .args(self.args(host, port))
+                .process_group(0)
+                .stdout(stdout)
+                .stderr(Stdio::piped()),
+            "cloudflared",
+            config,
+        )?;
+        let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+        Ok((child, Box::new(stderr)))
+    }
+    
+    fn url(&self, line: &str) -> Option<String> {
+        match self.named {
+            // A named tunnel's URL is known up front; it's usable once a connection registers
+            Some((_, ref url)) => is_tunnel_connected(line).then(|| url.clone()),
+            None => extract_tunnel_url(line),
+        }
+    }
+    
+    fn failure(&self, line: &str) -> Option<AppError> {
+        // Quick tunnels are rate-limited; waiting out the timeout won't help
+        is_rate_limited(line).then_some(AppError::TunnelRateLimited)
+    }
+}
+
+/// Public bore server used when `boreServer` isn't set
+const DEFAULT_BORE_SERVER: &str = "bore.pub";
+
+/// bore (github.com/ekzhang/bore): a plain TCP forward for networks that block cloudflared
+/// The URL is http on a random port of the bore server, so the API secret is the only protection
+struct Bore {
+    server: String,
+}
+
+impl TunnelProvider for Bore {
+    fn program(&self) -> &'static str {
+        "bore"
+    }
+    
+    fn args(&self, host: IpAddr, port: u16) -> Vec<String> {
+        vec![
+            "local".into(),
+            port.to_string(),
+            "--local-host".into(),
+            reachable_host(host).to_string(),
+            "--to".into(),
+            self.server.clone(),
+        ]
+    }
+    
+    fn start(&self, host: IpAddr, port: u16, log: Option<&File>, config: &Config) -> Result<(Child, Box<dyn std::io::Read + Send>), Box<dyn std::error::Error + Send + Sync>> {
+        // bore logs to stdout; stderr only carries crashes
+        let stderr = log
+            .and_then(|f| f.try_clone().ok())
+            .map(Stdio::from)
+            .unwrap_or_else(Stdio::null);
+        let mut child = spawn_resolved(
+            create_command("bore", config)
+                .args(self.args(host, port))
+                .process_group(0)
+                .stdout(Stdio::piped())
+                .stderr(stderr),
+            "bore",
+            config,
+        )?;
+        let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+        Ok((child, Box::new(stdout)))
+    }
+    
+    /// e.g. `INFO bore_cli::client: listening at bore.pub:41234`
+    fn url(&self, line: &str) -> Option<String> {
+        let line = strip_ansi(line);
+        let (_, rest) = line.split_once("listening at ")?;
+        let address = rest.split_whitespace().next()?;
+        address.contains(':').then(|| format!("http://{}", address))
+    }
+}
+
+/// First ingress hostname in a cloudflared config, e.g. `- hostname: mac.example.com`
+/// A line scan is enough for the flat YAML cloudflared configs use
+fn ingress_hostname(config: &str) -> Option<String> {
+    config.lines().find_map(|line| {
+        let line = line.trim().trim_start_matches('-').trim();
+        let host = line.strip_prefix("hostname:")?.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!host.is_empty()).then(|| host.to_string())
+    })
+}
+
+/// cloudflared output that means Cloudflare refused a quick tunnel for making too many
+const RATE_LIMIT_SIGNATURES: &[&str] = &["429 too many requests", "too many requests", "status code 429", "error code: 1015"];
+
+/// Whether a cloudflared log line reports being rate-limited
+fn is_rate_limited(line: &str) -> bool {
+    let line = line.to_lowercase();
+    RATE_LIMIT_SIGNATURES.iter().any(|signature| line.contains(signature))
+}
+
+/// Whether a cloudflared log line reports a live connection to Cloudflare's edge
+fn is_tunnel_connected(line: &str) -> bool {
+    line.contains("Registered tunnel connection")
+}
+
+/// Validate a named-tunnel config and work out the public URL it serves
+fn named_tunnel(path: &str) -> Result<(PathBuf, String), Box<dyn std::error::Error + Send + Sync>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Can't read cloudflared config {}: {}", path, e))?;
+    let hostname = ingress_hostname(&content)
+        .ok_or_else(|| format!("cloudflared config {} has no ingress hostname to serve", path))?;
+    Ok((PathBuf::from(path), format!("https://{}", hostname)))
+}
+
+/// Port the tunnel forwards to, per `tunnelTarget` (always the bridge in local-only mode)
+fn tunnel_target_port(config: &Config) -> u16 {
+    match config.tunnel_target() {
+        crate::config::TunnelTarget::Bridge => config.bridge_port(),
+        crate::config::TunnelTarget::Worker => config.worker_port(),
+    }
+}
+
+/// Abort a `start_tunnel_and_get_url` that is still waiting for its URL
+pub fn cancel_tunnel() {
+    TUNNEL_CANCEL.notify_waiters();
+}
+
+/// HTTP status of a GET to `url`, or 0 if nothing answered
+/// `api_secret` goes along as the bearer token the worker's protected routes expect, over stdin so it stays out of `ps`
+fn http_status(url: &str, api_secret: Option<&str>, config: &Config) -> u16 {
+    let child = create_command("curl", config)
+        .args(["-s", "-o", "/dev/null", "-w", "%{http_code}", "--max-time", "5", "-H", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        return 0;
+    };
+    if let (Some(secret), Some(mut stdin)) = (api_secret, child.stdin.take()) {
+        let _ = writeln!(stdin, "Authorization: Bearer {}", secret);
+    }
+    match child.wait_with_output() {
+        Ok(o) => String::from_utf8_lossy(&o.stdout).trim().parse().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+/// Whether a probe's status code means the server is up and handling requests
+fn status_means_up(code: u16) -> bool {
+    match code {
+        // curl's "no response": refused, timed out, or DNS failed
+        0 => false,
+        // Served normally, or redirected somewhere that will be
+        200..=399 => true,
+        // 401/403: an auth wall (e.g. a secret from another session) still means the worker is routing requests
+        // 404 and friends: the path isn't a route, but the worker itself answered
+        400..=499 => true,
+        // 5xx: the worker crashed on the request, or the tunnel can't reach its origin (502/530)
+        _ => false,
+    }
+}
+
+/// Probe a tunnel URL on the configured health path and report whether it is serving requests
+pub fn tunnel_health(url: &str, api_secret: Option<&str>, config: &Config) -> bool {
+    status_means_up(http_status(&format!("{}{}", url.trim_end_matches('/'), config.health_path()), api_secret, config))
+}
+
+/// Check whether a tracked process is still alive
+pub fn is_running(kind: ProcessKind) -> bool {
+    PROCESSES.is_running(kind)
+}
+
+/// Whether a start call launched something or found it already up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StartOutcome {
+    Started,
+    AlreadyRunning,
+}
+
+/// Whether everything `start_local_server` launches for the current mode is still running
+pub fn local_server_running() -> bool {
+    let local_only = crate::config::load_config().map(|c| c.is_local_only()).unwrap_or(false);
+    PROCESSES.server_running(local_only)
+}
+
+/// Send a signal to every process in a group
+fn signal_group(pgid: u32, signal: &str) {
+    let _ = Command::new("kill")
+        .args([signal, "--", &format!("-{}", pgid)])
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Stop a single tracked process, leaving the others running
+/// Each managed process leads its own process group, so the whole tree goes with it -
+/// `npx wrangler dev` in particular leaves workerd holding the worker port if only npx is killed.
+/// After this returns, `lsof -i:8787` should come back empty.
+pub fn stop(kind: ProcessKind) {
+    if PROCESSES.stop(kind) {
+        persist_runtime();
+    }
+}
+
+/// `stop` on the blocking pool, so its grace period doesn't hold up an async caller's runtime worker
+pub async fn stop_async(kind: ProcessKind) {
+    let _ = tokio::task::spawn_blocking(move || stop(kind)).await;
+}
+
+/// Processes `pause` suspends; cloudflared keeps running so the tunnel URL stays the same
+const PAUSABLE: [ProcessKind; 2] = [ProcessKind::LocalServer, ProcessKind::Bridge];
+
+/// Suspend the worker and bridge process groups (SIGSTOP)
+/// Requests through the tunnel then wait or time out instead of reaching the Mac
+pub fn pause() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pgids: Vec<u32> = PAUSABLE
+        .iter()
+        .filter(|&&kind| is_running(kind))
+        .filter_map(|&kind| PROCESSES.pid(kind))
+        .collect();
+    if pgids.is_empty() {
+        return Err("Nothing to pause - the local server isn't running".into());
+    }
+    for pgid in pgids {
+        signal_group(pgid, "-STOP");
+    }
+    log("info", "system", "Paused worker and bridge");
+    Ok(())
+}
+
+/// Continue the worker and bridge process groups after `pause` (SIGCONT)
+pub fn resume() {
+    for kind in PAUSABLE {
+        if let Some(pgid) = PROCESSES.pid(kind) {
+            signal_group(pgid, "-CONT");
+        }
+    }
+    log("info", "system", "Resumed worker and bridge");
+}
+
+/// Collect any tracked processes that have exited on their own
+/// Returns the kinds that died since the last call
+pub fn reap_exited() -> Vec<ProcessKind> {
+    let mut exited = Vec::new();
+    for kind in ProcessKind::ALL {
+        if let Some(status) = PROCESSES.reap(kind) {
+            log("warn", kind.log_name(), &format!("process exited unexpectedly ({})", status));
+            exited.push(kind);
+        }
+    }
+    if !exited.is_empty() {
+        persist_runtime();
+    }
+    exited
+}
+
+/// When the running system was first started, unaffected by pause/resume and restarts
+pub fn started_at() -> Option<std::time::SystemTime> {
+    PROCESSES.started_at()
+}
+
+/// RFC 3339 form of a start time, for the frontend
+pub fn format_started_at(time: std::time::SystemTime) -> String {
+    format_timestamp(time)
+}
+
+/// Start counting uptime and restarts afresh
+pub fn reset_process_stats() {
+    PROCESSES.reset_stats();
+}
+
+/// Liveness of each managed process
+pub fn process_statuses() -> Vec<ProcessStatus> {
+    ProcessKind::ALL
+        .iter()
+        .map(|&kind| ProcessStatus {
+            name: kind,
+            running: is_running(kind),
+            pid: PROCESSES.pid(kind),
+            command: PROCESSES.command(kind),
+            log_path: PROCESSES.log_path(kind),
+            restarts: PROCESSES.restarts(kind),
+        })
+        .collect()
+}
+
+/// Exit status, liveness, and recent output for each managed process
+pub fn process_diagnostics() -> Vec<ProcessDiagnostics> {
+    ProcessKind::ALL
+        .iter()
+        .map(|&kind| {
+            let running = is_running(kind);
+            let last = PROCESSES.last_exit(kind);
+            ProcessDiagnostics {
+                name: kind,
+                running,
+                last_exit_code: last.and_then(|s| s.code()),
+                last_signal: last.and_then(|s| s.signal()),
+                restarts: PROCESSES.restarts(kind),
+                log_tail: log_tail(kind, LOG_TAIL_LINES),
+            }
+        })
+        .collect()
+}
+
+pub async fn stop_all() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Each stop can wait out a grace period, so keep them off the runtime
+    tokio::task::spawn_blocking(|| {
+        for kind in ProcessKind::ALL {
+            stop(kind);
+            unfollow_log(kind);
+        }
+    })
+    .await?;
+    Ok(())
+}
+
+/// A leftover process from an earlier session that we no longer track
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanProcess {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// A process we spawned, as recorded in runtime.json for the next launch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeRecord {
+    kind: ProcessKind,
+    pid: u32,
+    /// Full command line, so a reused PID isn't mistaken for ours
+    command: String,
+}
+
+fn runtime_path() -> Option<PathBuf> {
+    crate::config::get_config_dir().ok().map(|dir| dir.join("runtime.json"))
+}
+
+/// Full command line of a running process, or None if it's gone
+fn process_command(pid: u32) -> Option<String> {
+    let output = Command::new("ps").args(["-o", "command=", "-p", &pid.to_string()]).output().ok()?;
+    let command = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !command.is_empty()).then_some(command)
+}
+
+/// Record every tracked process so a launch after a crash can find them
+/// The file is removed once nothing is running, i.e. after a clean shutdown
+fn persist_runtime() {
+    let Some(path) = runtime_path() else { return };
+    let records: Vec<RuntimeRecord> = ProcessKind::ALL
+        .iter()
+        .filter_map(|&kind| {
+            let pid = PROCESSES.pid(kind)?;
+            Some(RuntimeRecord { kind, pid, command: process_command(pid)? })
+        })
+        .collect();
+    
+    if records.is_empty() {
+        let _ = std::fs::remove_file(path);
+    } else if let Ok(content) = serde_json::to_string_pretty(&records) {
+        let _ = crate::config::write_file(&path, content);
+    }
+}
+
+/// Whether a recorded process is still the one we spawned
+fn still_ours(record: &RuntimeRecord, current_command: Option<&str>) -> bool {
+    current_command == Some(record.command.as_str())
+}
+
+/// Processes recorded by a previous session (that crashed) which are still running
+pub fn leftover_processes() -> Vec<OrphanProcess> {
+    let records: Vec<RuntimeRecord> = runtime_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let tracked = PROCESSES.pids();
+    
+    records
+        .into_iter()
+        .filter(|record| !tracked.contains(&record.pid))
+        .filter(|record| still_ours(record, process_command(record.pid).as_deref()))
+        .map(|record| OrphanProcess { pid: record.pid, command: record.command })
+        .collect()
+}
+
+/// Whether a command line is one we spawn ourselves
+/// Matches on the exact arguments we pass so unrelated node/cloudflared processes are left alone
+fn is_our_command(command: &str, worker_port: u16, bridge_port: u16) -> bool {
+    let wrangler = command.contains("wrangler dev") && command.contains(&format!("--port {}", worker_port));
+    let bridge = command.contains("node") && command.contains("dist/bridge/http-server.js");
+    let tunnel = command.contains("cloudflared")
+        && command.contains("--url http://")
+        && [worker_port, bridge_port].iter().any(|port| command.contains(&format!(":{}", port)));
+    wrangler || bridge || tunnel
+}
+
+/// Processes matching our command lines that aren't the ones we're currently tracking
+pub fn find_orphans() -> Vec<OrphanProcess> {
+    let (worker_port, bridge_port) = crate::config::load_config()
+        .map(|c| (c.worker_port(), c.bridge_port()))
+        .unwrap_or((crate::config::DEFAULT_WORKER_PORT, crate::config::DEFAULT_BRIDGE_PORT));
+    let tracked = PROCESSES.pids();
+    
+    let output = match Command::new("ps").args(["-axo", "pid=,command="]).output() {
+        Ok(o) => String::from_utf8_lossy(&o.stdout).to_string(),
+        Err(_) => return Vec::new(),
+    };
+    
+    let mut orphans: Vec<OrphanProcess> = output
+        .lines()
+        .filter_map(|line| {
+            let (pid, command) = line.trim().split_once(' ')?;
+            let pid: u32 = pid.parse().ok()?;
+            Some(OrphanProcess { pid, command: command.trim().to_string() })
+        })
+        .filter(|p| p.pid != std::process::id() && !tracked.contains(&p.pid))
+        .filter(|p| is_our_command(&p.command, worker_port, bridge_port))
+        .collect();
+    
+    // Anything a crashed session recorded counts too, even if it was started with a custom command
+    for leftover in leftover_processes() {
+        if !orphans.iter().any(|p| p.pid == leftover.pid) {
+            orphans.push(leftover);
+        }
+    }
+    orphans
+}
+
+/// Terminate every orphaned process, returning the PIDs that were signalled
+pub fn kill_orphans() -> Vec<u32> {
+    let killed = find_orphans()
+        .into_iter()
+        .filter(|p| {
+            Command::new("kill")
+                .arg(p.pid.to_string())
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false)
+        })
+        .map(|p| p.pid)
+        .collect();
+    
+    // Drop the crashed session's records now that its processes are gone
+    persist_runtime();
+    killed
+}
+
+/// Callback reporting `(stage, status)` as a multi-step operation progresses
+pub type Progress<'a> = &'a (dyn Fn(&str, &str) + Send + Sync);
+
+/// Find the `*.workers.dev` URL wrangler prints after a successful deploy
+fn parse_deployed_url(output: &str) -> Option<String> {
+    strip_ansi(output)
+        .split_whitespace()
+        .find(|word| word.starts_with("https://") && word.ends_with(".workers.dev"))
+        .map(|word| word.to_string())
+}
+
+/// Turn wrangler's output into an error message, recognising the common failures
+fn describe_wrangler_failure(output: &str) -> String {
+    if output.contains("Could not resolve") {
+        return "Dependencies not installed. Run: cd cloudflare-agent && npm install".to_string();
+    }
+    if output.contains("not logged in") || output.contains("not authenticated") || output.contains("authentication") {
+        return "Not logged in to Cloudflare. Run: npx wrangler login".to_string();
+    }
+    
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(20)..].join("\n")
+}
+
+/// A wrangler invocation in the worker directory, pinned to the chosen Cloudflare account
+fn wrangler_command(agent_dir: &PathBuf, account_id: Option<&str>, args: &[&str], config: &Config) -> Command {
+    let mut cmd = create_command("npx", config);
+    cmd.arg("wrangler").args(args).current_dir(agent_dir);
+    if let Some(id) = account_id {
+        cmd.env("CLOUDFLARE_ACCOUNT_ID", id);
+    }
+    cmd
+}
+
+/// The configured Cloudflare account, rejecting ids that can't be valid
+fn selected_account_id(config: &Config) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    match config.cloudflare_account_id.as_deref() {
+        Some(id) if !crate::config::is_valid_account_id(id) => {
+            Err(format!("Saved Cloudflare account id '{}' is not a 32-character hex id", id).into())
+        }
+        other => Ok(other.map(str::to_string)),
+    }
+}
+
+/// What a deploy attempt actually created, so failures can be cleaned up precisely
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployReport {
+    pub worker_deployed: bool,
+    pub url: Option<String>,
+    pub secrets_set: Vec<String>,
+    pub secrets_failed: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Set one secret on the deployed worker, passing the value over stdin
+fn put_worker_secret(agent_dir: &PathBuf, account_id: Option<&str>, name: &str, value: &str, config: &Config) -> Result<(), String> {
+    let mut child = wrangler_command(agent_dir, account_id, &["secret", "put", name], config)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", value).map_err(|e| e.to_string())?;
+    }
+    
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(describe_wrangler_failure(&String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+/// Deploy the Cloudflare Worker with `wrangler deploy`, then push its secrets
+/// Secrets are only pushed after wrangler confirms the deploy, and config is only
+/// marked as deployed once every secret is in place
+pub fn deploy_worker(api_secret: &str, progress: Progress) -> Result<DeployReport, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let project_root = find_project_root(Some(&config))?;
+    let agent_dir = project_root.join("cloudflare-agent");
+    let api_key = config.anthropic_key.clone().ok_or("No Anthropic API key saved")?;
+    let account_id = selected_account_id(&config)?;
+    let mut report = DeployReport::default();
+    
+    progress("deploy", "running");
+    let output = wrangler_command(&agent_dir, account_id.as_deref(), &["deploy"], &config).output()?;
+    let combined = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    
+    if !output.status.success() {
+        progress("deploy", "failed");
+        report.error = Some(format!("wrangler deploy failed: {}", describe_wrangler_failure(&combined)));
+        return Ok(report);
+    }
+    
+    let url = match parse_deployed_url(&combined) {
+        Some(url) => url,
+        None => {
+            progress("deploy", "failed");
+            report.error = Some("Deploy finished but wrangler didn't report a workers.dev URL".to_string());
+            return Ok(report);
+        }
+    };
+    report.worker_deployed = true;
+    report.url = Some(url.clone());
+    progress("deploy", "done");
+    
+    progress("secrets", "running");
+    let secrets = [
+        ("ANTHROPIC_API_KEY", api_key.as_str()),
+        ("BRIDGE_AUTH_TOKEN", api_secret),
+        ("API_SECRET", api_secret),
+    ];
+    for (name, value) in secrets {
+        match put_worker_secret(&agent_dir, account_id.as_deref(), name, value, &config) {
+            Ok(()) => report.secrets_set.push(name.to_string()),
+            Err(e) => {
+                report.secrets_failed.push(name.to_string());
+                report.error.get_or_insert(format!("Failed to set {}: {}", name, e));
+            }
+        }
+    }
+    progress("secrets", if report.secrets_failed.is_empty() { "done" } else { "failed" });
+    
+    // Record the worker either way so a half-configured one can be cleaned up later
+    let mut config = crate::config::load_config().map_err(|e| e.to_string())?;
+    config.deployed = Some(report.secrets_failed.is_empty());
+    config.deployed_url = Some(url);
+    crate::config::save_config(&config).map_err(|e| e.to_string())?;
+    
+    Ok(report)
+}
+
+/// Push a new API secret to the deployed worker
+/// Returns the names of the secrets that were updated, or the first failure
+pub fn push_auth_secrets(api_secret: &str) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let agent_dir = find_project_root(Some(&config))?.join("cloudflare-agent");
+    let account_id = selected_account_id(&config)?;
+    
+    let mut updated = Vec::new();
+    for name in ["BRIDGE_AUTH_TOKEN", "API_SECRET"] {
+        put_worker_secret(&agent_dir, account_id.as_deref(), name, api_secret, &config)
+            .map_err(|e| format!("Failed to set {} (already updated: {:?}): {}", name, updated, e))?;
+        updated.push(name.to_string());
+    }
+    Ok(updated)
+}
+
+/// What a deployed worker reports about itself at /api/status
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeployedStatus {
+    bridge_url: Option<String>,
+}
+
+/// Whether the deployed worker is set up for the tunnel and secret running here
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentCheck {
+    pub in_sync: bool,
+    /// False when the worker couldn't be asked, e.g. a deploy from before /api/status existed
+    pub checked: bool,
+    pub differences: Vec<String>,
+    /// What to do about it
+    pub suggestion: Option<String>,
+}
+
+/// GET `url` with the API secret as bearer token, returning the status code (0 if unreachable) and body
+fn http_get(url: &str, api_secret: Option<&str>, config: &Config) -> (u16, String) {
+    let headers: Vec<String> = api_secret.map(|secret| format!("Authorization: Bearer {}", secret)).into_iter().collect();
+    curl_get(url, &headers, config)
+}
+
+/// GET `url` with extra headers, returning the status code (0 if unreachable) and body
+/// The headers go to curl on stdin so secrets in them never show up in `ps`
+fn curl_get(url: &str, headers: &[String], config: &Config) -> (u16, String) {
+    let child = create_command("curl", config)
+        .args(["-s", "--max-time", "10", "-H", "@-", "-w", "\n%{http_code}", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        return (0, String::new());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        for header in headers {
+            let _ = writeln!(stdin, "{}", header);
+        }
+    }
+    let Ok(output) = child.wait_with_output() else {
+        return (0, String::new());
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, code) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    (code.trim().parse().unwrap_or(0), body.to_string())
+}
+
+/// Requests sent down each path by `measure_latency`
+const LATENCY_SAMPLES: usize = 5;
+
+/// Longest one latency sample may take before it counts as failed
+const LATENCY_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Round-trip times for one path, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub url: String,
+    pub ok: usize,
+    pub failed: usize,
+    /// None when every request failed
+    pub min_ms: Option<f64>,
+    pub median_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+/// Latency straight to the local server and through the tunnel, to tell tunnel lag from local slowness
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyReport {
+    pub local: LatencyStats,
+    /// None when no tunnel is running
+    pub tunnel: Option<LatencyStats>,
+    /// What the tunnel adds, median against median
+    pub overhead_ms: Option<f64>,
+}
+
+/// Min/median/max of the samples that succeeded
+fn latency_stats(url: &str, samples: &[Option<f64>]) -> LatencyStats {
+    let mut times: Vec<f64> = samples.iter().flatten().copied().collect();
+    times.sort_by(f64::total_cmp);
+    let median = match times.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(times[n / 2]),
+        n => Some((times[n / 2 - 1] + times[n / 2]) / 2.0),
+    };
+    LatencyStats {
+        url: url.to_string(),
+        ok: times.len(),
+        failed: samples.len() - times.len(),
+        min_ms: times.first().copied(),
+        median_ms: median,
+        max_ms: times.last().copied(),
+    }
+}
+
+/// How long one authed GET of `url` took, or None if it got no usable answer
+fn timed_request(url: &str, api_secret: Option<&str>, config: &Config) -> Option<f64> {
+    let mut child = create_command("curl", config)
+        .args(["-s", "-o", "/dev/null", "--max-time", &LATENCY_REQUEST_TIMEOUT_SECS.to_string()])
+        .args(["-H", "@-", "-w", "%{http_code} %{time_total}", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    if let (Some(secret), Some(mut stdin)) = (api_secret, child.stdin.take()) {
+        let _ = writeln!(stdin, "Authorization: Bearer {}", secret);
+    }
+    let output = child.wait_with_output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (code, secs) = stdout.trim().split_once(' ')?;
+    if !status_means_up(code.parse().unwrap_or(0)) {
+        return None;
+    }
+    secs.parse::<f64>().ok().map(|secs| (secs * 10_000.0).round() / 10.0)
+}
+
+fn sample_latency(url: &str, api_secret: Option<&str>, config: &Config) -> LatencyStats {
+    let samples: Vec<Option<f64>> = (0..LATENCY_SAMPLES).map(|_| timed_request(url, api_secret, config)).collect();
+    latency_stats(url, &samples)
+}
+
+/// Time a few requests to the health path locally and, if there's a tunnel, through it
+pub fn measure_latency(tunnel_url: Option<&str>, api_secret: Option<&str>) -> Result<LatencyReport, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let bind_host = config.bind_host()?;
+    let port = tunnel_target_port(&config);
+    if !port_is_listening(bind_host, port) {
+        return Err(AppError::LocalServerNotRunning { port }.into());
+    }
+    let health_path = config.health_path();
+    
+    let local = sample_latency(&format!("{}{}", local_url(bind_host, port), health_path), api_secret, &config);
+    let tunnel = tunnel_url.map(|url| sample_latency(&format!("{}{}", url.trim_end_matches('/'), health_path), api_secret, &config));
+    let overhead_ms = tunnel
+        .as_ref()
+        .and_then(|tunnel| Some(tunnel.median_ms? - local.median_ms?))
+        .map(|ms| (ms * 10.0).round() / 10.0);
+    Ok(LatencyReport { local, tunnel, overhead_ms })
+}
+
+/// Whose credentials an auth failure is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthFailure {
+    /// The worker turned away this session's API secret
+    ApiSecret,
+    /// Anthropic turned away the API key: revoked, rotated, or out of credit
+    AnthropicKey,
+}
+
+impl AuthFailure {
+    pub fn message(self) -> &'static str {
+        match self {
+            AuthFailure::ApiSecret => "The worker rejected this session's API secret - stop and start SYSTEM to sync it",
+            AuthFailure::AnthropicKey => "Anthropic rejected your API key - it may have been revoked or rotated; enter a new one",
+        }
+    }
+}
+
+/// Tell an auth failure apart by its payload: Anthropic's errors carry a typed error object,
+/// the worker's auth wall just says "Unauthorized"
+fn classify_auth_failure(code: u16, body: &str) -> Option<AuthFailure> {
+    if !matches!(code, 401 | 403) {
+        return None;
+    }
+    let anthropic = ["authentication_error", "permission_error", "invalid x-api-key"];
+    if anthropic.iter().any(|marker| body.contains(marker)) {
+        Some(AuthFailure::AnthropicKey)
+    } else {
+        Some(AuthFailure::ApiSecret)
+    }
+}
+
+/// Probe both sides of auth: the tunnel's authenticated status route with the session secret,
+/// then the Anthropic API with the saved key
+pub fn check_auth(tunnel_url: Option<&str>, api_secret: Option<&str>, api_key: Option<&str>, base_url: &str, config: &Config) -> Option<AuthFailure> {
+    if let Some(url) = tunnel_url {
+        let (code, body) = http_get(&format!("{}/api/status", url.trim_end_matches('/')), api_secret, config);
+        if let Some(failure) = classify_auth_failure(code, &body) {
+            return Some(failure);
+        }
+    }
+    let api_key = api_key?;
+    let headers = [format!("x-api-key: {}", api_key.trim()), "anthropic-version: 2023-06-01".to_string()];
+    let (code, body) = curl_get(&format!("{}/v1/models", base_url), &headers, config);
+    classify_auth_failure(code, &body)
+}
+
+/// How many failed checks in a row count as a persistent auth problem rather than a blip
+const AUTH_FAILURE_THRESHOLD: u32 = 2;
+
+/// Turns a stream of auth checks into one report per persistent failure
+#[derive(Debug, Default)]
+pub struct AuthWatch {
+    last: Option<AuthFailure>,
+    streak: u32,
+    reported: bool,
+}
+
+impl AuthWatch {
+    /// Record a check; returns the failure once it has persisted long enough, and only once
+    pub fn update(&mut self, result: Option<AuthFailure>) -> Option<AuthFailure> {
+        if result != self.last {
+            self.last = result;
+            self.streak = 0;
+            self.reported = false;
+        }
+        let failure = result?;
+        self.streak += 1;
+        if self.streak >= AUTH_FAILURE_THRESHOLD && !self.reported {
+            self.reported = true;
+            return Some(failure);
+        }
+        None
+    }
+}
+
+/// Judge a deployed worker's /api/status response against the local tunnel
+fn compare_deployment(code: u16, body: &str, tunnel_url: Option<&str>) -> DeploymentCheck {
+    let unchecked = |difference: String, suggestion: &str| DeploymentCheck {
+        in_sync: false,
+        checked: false,
+        differences: vec![difference],
+        suggestion: Some(suggestion.to_string()),
+    };
+    let status: DeployedStatus = match code {
+        200..=299 => match serde_json::from_str(body) {
+            Ok(status) => status,
+            Err(_) => return unchecked("The worker's status response isn't valid JSON".into(), "Redeploy the worker"),
+        },
+        401 | 403 => {
+            return DeploymentCheck {
+                in_sync: false,
+                checked: true,
+                differences: vec!["The worker's API_SECRET doesn't match this session's secret".into()],
+                suggestion: Some("Rotate secrets with \"push to worker\" on, or redeploy".into()),
+            };
+        }
+        404 => return unchecked(
+            "This deploy doesn't report its status".into(),
+            "Redeploy the worker to enable this check",
+        ),
+        0 => return unchecked("Couldn't reach the deployed worker".into(), "Check your connection and the deployed URL"),
+        code => return unchecked(format!("The worker answered with HTTP {}", code), "Check the worker's logs with `wrangler tail`"),
+    };
+    
+    let normalize = |url: &str| url.trim().trim_end_matches('/').to_string();
+    let mut differences = Vec::new();
+    match (status.bridge_url.as_deref().map(normalize), tunnel_url.map(normalize)) {
+        (Some(deployed), Some(local)) if deployed != local => {
+            differences.push(format!("The worker's BRIDGE_URL is {} but the tunnel is at {}", deployed, local));
+        }
+        (deployed, None) => {
+            differences.push(format!(
+                "No tunnel is running here, so the worker's BRIDGE_URL ({}) leads nowhere",
+                deployed.unwrap_or_else(|| "unset".into())
+            ));
+        }
+        (None, Some(local)) => differences.push(format!("The worker has no BRIDGE_URL; the tunnel is at {}", local)),
+        _ => {}
+    }
+    
+    DeploymentCheck {
+        in_sync: differences.is_empty(),
+        checked: true,
+        suggestion: (!differences.is_empty()).then(|| "Start the tunnel and update BRIDGE_URL on the worker, or redeploy".to_string()),
+        differences,
+    }
+}
+
+/// Ask the deployed worker what it's configured with and compare it to the local tunnel and secret
+pub fn verify_deployment(deployed_url: &str, api_secret: Option<&str>, tunnel_url: Option<&str>, config: &Config) -> DeploymentCheck {
+    let (code, body) = http_get(&format!("{}/api/status", deployed_url.trim_end_matches('/')), api_secret, config);
+    compare_deployment(code, &body, tunnel_url)
+}
+
+/// Delete a worker (and with it, its secrets) left behind by a failed deploy
+pub fn cleanup_failed_deploy() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let project_root = find_project_root(Some(&config))?;
+    let account_id = selected_account_id(&config)?;
+    
+    let output = wrangler_command(&project_root.join("cloudflare-agent"), account_id.as_deref(), &["delete", "--force"], &config)
+        .output()?;
+    if !output.status.success() {
+        let combined = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(format!("wrangler delete failed: {}", describe_wrangler_failure(&combined)).into());
+    }
+    
+    let mut config = crate::config::load_config().map_err(|e| e.to_string())?;
+    config.deployed = Some(false);
+    config.deployed_url = None;
+    crate::config::save_config(&config).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Secrets a deploy puts on the worker
+const WORKER_SECRET_NAMES: &[&str] = &["ANTHROPIC_API_KEY", "BRIDGE_AUTH_TOKEN", "API_SECRET"];
+
+/// A secret set on the deployed worker; wrangler never reveals values, only names
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerSecret {
+    pub name: String,
+    /// Whether it's one of the secrets a deploy sets, rather than something added by hand
+    pub managed: bool,
+}
+
+#[derive(Deserialize)]
+struct ListedSecret {
+    name: String,
+}
+
+/// Parse `wrangler secret list`, which prints a JSON array after wrangler's banner
+fn parse_secret_list(output: &str) -> Result<Vec<WorkerSecret>, String> {
+    let output = strip_ansi(output);
+    let start = output.find('[').ok_or("wrangler didn't print a secret list")?;
+    let end = output.rfind(']').filter(|&end| end > start).ok_or("wrangler didn't print a secret list")?;
+    let listed: Vec<ListedSecret> = serde_json::from_str(&output[start..=end])
+        .map_err(|e| format!("Couldn't read wrangler's secret list: {}", e))?;
+    Ok(listed
+        .into_iter()
+        .map(|secret| WorkerSecret { managed: WORKER_SECRET_NAMES.contains(&secret.name.as_str()), name: secret.name })
+        .collect())
+}
+
+/// Secret names wrangler accepts; anything else is refused before it reaches the command line
+fn is_valid_secret_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Run a `wrangler secret` subcommand against the cloudflare-agent worker, with its output kept for errors
+fn run_wrangler_secret(args: &[&str], stdin: Option<&str>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let agent_dir = find_project_root(Some(&config))?.join("cloudflare-agent");
+    let account_id = selected_account_id(&config)?;
+    
+    let mut child = wrangler_command(&agent_dir, account_id.as_deref(), args, &config)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    let combined = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !output.status.success() {
+        return Err(format!("wrangler secret {} failed: {}", args[1], describe_wrangler_failure(&combined)).into());
+    }
+    Ok(combined)
+}
+
+/// Secrets currently set on the cloudflare-agent worker
+pub fn list_worker_secrets() -> Result<Vec<WorkerSecret>, Box<dyn std::error::Error + Send + Sync>> {
+    let output = run_wrangler_secret(&["secret", "list"], None)?;
+    Ok(parse_secret_list(&output)?)
+}
+
+/// Remove one secret from the cloudflare-agent worker, e.g. one a failed deploy left behind
+pub fn delete_worker_secret(name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !is_valid_secret_name(name) {
+        return Err(format!("\"{}\" isn't a worker secret name", name).into());
+    }
+    // wrangler asks to confirm the delete; the caller already has
+    run_wrangler_secret(&["secret", "delete", name], Some("y\n"))?;
+    Ok(())
+}
+
+/// A Cloudflare account wrangler can deploy to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareAccount {
+    pub name: String,
+    pub id: String,
+}
+
+/// Whether wrangler is logged in, and as whom
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WranglerAuth {
+    pub authenticated: bool,
+    pub account_id: Option<String>,
+    pub email: Option<String>,
+    pub accounts: Vec<CloudflareAccount>,
+    /// How to fix things when not authenticated
+    pub hint: Option<String>,
+    /// wrangler's stderr, included when its output couldn't be understood
+    pub stderr: Option<String>,
+}
+
+/// Pull the email and account table out of `wrangler whoami` output
+fn parse_whoami(output: &str) -> (Option<String>, Vec<CloudflareAccount>) {
+    let output = strip_ansi(output);
+    let email = output.lines().find_map(|line| {
+        let (_, rest) = line.split_once("associated with the email ")?;
+        Some(rest.trim().trim_end_matches('.').to_string())
+    });
+    
+    // Table rows look like: │ Account Name │ 0123...cdef │
+    let accounts = output
+        .lines()
+        .filter_map(|line| {
+            let cells: Vec<&str> = line.split('│').map(str::trim).filter(|c| !c.is_empty()).collect();
+            match cells.as_slice() {
+                [name, id] if crate::config::is_valid_account_id(id) => Some(CloudflareAccount {
+                    name: name.to_string(),
+                    id: id.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    
+    (email, accounts)
+}
+
+/// Ask wrangler who it's logged in as
+pub fn check_wrangler_auth() -> Result<WranglerAuth, Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let mut cmd = create_command("npx", &config);
+    cmd.args(["wrangler", "whoami"]);
+    if let Ok(root) = find_project_root(Some(&config)) {
+        cmd.current_dir(root.join("cloudflare-agent"));
+    }
+    let output = cmd.output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    
+    let (email, accounts) = parse_whoami(&stdout);
+    let not_authenticated = stdout.contains("not authenticated") || stderr.contains("not authenticated");
+    let authenticated = !not_authenticated && (email.is_some() || !accounts.is_empty());
+    
+    Ok(WranglerAuth {
+        authenticated,
+        account_id: accounts.first().map(|a| a.id.clone()),
+        email,
+        hint: (!authenticated).then(|| "Run `npx wrangler login` to sign in to Cloudflare".to_string()),
+        // Neither logged in nor clearly logged out - hand back what wrangler said
+        stderr: (!authenticated && !not_authenticated).then_some(stderr),
+        accounts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_url_from_plain_banner() {
+        let line = "2024-01-01T00:00:00Z INF |  https://quiet-river-1234.trycloudflare.com                                   |";
+        assert_eq!(
+            extract_tunnel_url(line).as_deref(),
+            Some("https://quiet-river-1234.trycloudflare.com")
+        );
+    }
+
+    #[test]
+    fn extracts_url_from_colorized_line() {
+        let line = "\u{1b}[90m2024-01-01T00:00:00Z\u{1b}[0m \u{1b}[32mINF\u{1b}[0m |  \u{1b}[1mhttps://quiet-river-1234.trycloudflare.com\u{1b}[0m  |";
+        assert_eq!(
+            extract_tunnel_url(line).as_deref(),
+            Some("https://quiet-river-1234.trycloudflare.com")
+        );
+    }
+
+    #[test]
+    fn extracts_url_embedded_after_color_reset() {
+        let line = "\u{1b}[0m\u{1b}[36mVisit it at:\u{1b}[0mhttps://quiet-river-1234.trycloudflare.com\u{1b}[0m";
+        assert_eq!(
+            extract_tunnel_url(line).as_deref(),
+            Some("https://quiet-river-1234.trycloudflare.com")
+        );
+    }
+
+    #[test]
+    fn ignores_lines_without_a_url() {
+        assert_eq!(extract_tunnel_url("\u{1b}[32mINF\u{1b}[0m Requesting new quick Tunnel on trycloudflare.com..."), None);
+        assert_eq!(extract_tunnel_url("INF Starting tunnel"), None);
+    }
+
+    #[test]
+    fn malformed_urls_give_way_to_a_later_valid_one() {
+        for bad in [
+            "https://quiet-river-1234.trycloudflare.com.",
+            "https://quiet-river-1234.trycloudflare.com,",
+            "https://.trycloudflare.com",
+            "https://trycloudflare.com",
+            "https://quiet-ri\u{1b}[0mver.trycloudflare.com",
+            "https://quiet_river.trycloudflare.com",
+            "https://trycloudflare.com.evil.example",
+        ] {
+            assert_eq!(quick_tunnel_origin(bad), None, "{}", bad);
+        }
+        
+        let lines = [
+            "INF |  https://quiet-river-1234.trycloudflare.com.  |",
+            "INF |  https://quiet-riv",
+            "INF |  https://quiet-river-1234.trycloudflare.com  |",
+        ];
+        assert_eq!(
+            lines.iter().find_map(|line| extract_tunnel_url(line)).as_deref(),
+            Some("https://quiet-river-1234.trycloudflare.com")
+        );
+        // Within a line, too
+        assert_eq!(
+            extract_tunnel_url("https://x.trycloudflare.com; see https://quiet-river-1234.trycloudflare.com/").as_deref(),
+            Some("https://quiet-river-1234.trycloudflare.com")
+        );
+    }
+
+    /// Fresh, empty directory under the system temp dir
+    fn temp_tree(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("system-test-{}-{}", name, generate_token()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_bridge_dist_is_reported() {
+        let root = temp_tree("no-dist");
+        let bridge = CommandPlan {
+            program: "node".to_string(),
+            args: vec!["dist/bridge/http-server.js".into()],
+            cwd: root.clone(),
+            env: Vec::new(),
+        };
+        
+        let err = ensure_bridge_built(&bridge).unwrap_err();
+        assert!(matches!(err, AppError::BridgeNotBuilt { ref entry } if entry.ends_with("dist/bridge/http-server.js")));
+        
+        std::fs::create_dir_all(root.join("dist/bridge")).unwrap();
+        std::fs::write(root.join("dist/bridge/http-server.js"), "").unwrap();
+        assert!(ensure_bridge_built(&bridge).is_ok());
+        
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn scan_finds_projects_within_depth() {
+        let root = temp_tree("scan-depth");
+        std::fs::create_dir_all(root.join("dev/foo/system/cloudflare-agent")).unwrap();
+        std::fs::create_dir_all(root.join("a/b/c/d/system/cloudflare-agent")).unwrap();
+        
+        let found = scan_for_projects(std::slice::from_ref(&root), 3);
+        assert_eq!(found, vec![root.join("dev/foo/system")]);
+        
+        let deeper = scan_for_projects(std::slice::from_ref(&root), 5);
+        assert_eq!(deeper.len(), 2);
+        
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scan_skips_heavy_and_hidden_dirs() {
+        let root = temp_tree("scan-skip");
+        std::fs::create_dir_all(root.join("node_modules/system/cloudflare-agent")).unwrap();
+        std::fs::create_dir_all(root.join(".cache/system/cloudflare-agent")).unwrap();
+        std::fs::create_dir_all(root.join("Library/system/cloudflare-agent")).unwrap();
+        
+        assert!(scan_for_projects(std::slice::from_ref(&root), 3).is_empty());
+        
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scan_dedupes_overlapping_bases() {
+        let root = temp_tree("scan-dedupe");
+        std::fs::create_dir_all(root.join("Projects/system/cloudflare-agent")).unwrap();
+        
+        let found = scan_for_projects(&[root.join("Projects"), root.clone()], 3);
+        assert_eq!(found, vec![root.join("Projects/system")]);
+        
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn local_url_reaches_the_bound_interface() {
+        assert_eq!(local_url(crate::config::DEFAULT_BIND_HOST, 8787), "http://localhost:8787");
+        assert_eq!(local_url(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8787), "http://localhost:8787");
+        assert_eq!(local_url("192.168.1.20".parse().unwrap(), 9000), "http://192.168.1.20:9000");
+        assert_eq!(local_url(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 8787), "http://[::1]:8787");
+    }
+
+    #[test]
+    fn wrangler_dev_binds_to_host_and_port() {
+        let plan = wrangler_dev_plan(PathBuf::from("/p/cloudflare-agent"), "0.0.0.0".parse().unwrap(), 8788);
+        assert_eq!(plan.program, "npx");
+        assert_eq!(plan.args, vec!["wrangler", "dev", "--ip", "0.0.0.0", "--port", "8788"]);
+        assert_eq!(plan.cwd, PathBuf::from("/p/cloudflare-agent"));
+    }
+
+    /// Lay down the files `check_project_compatibility` looks for, at the given version
+    fn write_project_markers(root: &std::path::Path, version: &str) {
+        for marker in PROJECT_MARKERS {
+            let path = root.join(marker);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "").unwrap();
+        }
+        std::fs::write(root.join("package.json"), format!(r#"{{"name": "system-mac", "version": "{}"}}"#, version)).unwrap();
+    }
+
+    fn project_config(name: &str, mode: Option<&str>) -> (PathBuf, Config) {
+        let root = temp_tree(name);
+        write_project_markers(&root, MIN_PROJECT_VERSION);
+        let config = Config {
+            project_root: Some(root.to_string_lossy().to_string()),
+            mode: mode.map(String::from),
+            ..Default::default()
+        };
+        (root, config)
+    }
+
+    #[test]
+    fn project_paths_with_spaces_and_unicode_are_kept_intact() {
+        let parent = temp_tree("My Projects");
+        let root = parent.join("sÿstem – ünïcode");
+        write_project_markers(&root, MIN_PROJECT_VERSION);
+        let config = Config {
+            anthropic_key: Some("sk-ant-test".into()),
+            project_root: Some(root.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        
+        let plan = build_plan(Some(&config), |key, port| dev_vars_content(key, port, "secret")).unwrap();
+        let root = std::fs::canonicalize(&root).unwrap();
+        assert_eq!(plan.project_root, root);
+        assert_eq!(plan.bridge.cwd, root);
+        assert_eq!(plan.wrangler.as_ref().unwrap().cwd, root.join("cloudflare-agent"));
+        // The root is only ever the working directory, never spliced into arguments
+        assert!(plan.bridge.args.iter().all(|arg| !arg.contains("My Projects")));
+        
+        write_project_secrets(&plan, "secret").unwrap();
+        assert!(std::fs::read_to_string(root.join("cloudflare-agent").join(".dev.vars")).unwrap().contains("sk-ant-test"));
+        assert!(root.join("bridge.config.json").exists());
+        let _ = std::fs::remove_dir_all(&parent);
+    }
+
+    #[test]
+    fn local_only_mode_runs_just_the_bridge() {
+        let (root, config) = project_config("local-only", Some(crate::config::LOCAL_ONLY_MODE));
+        let plan = build_plan(Some(&config), |_, _| String::new()).unwrap();
+        assert!(plan.wrangler.is_none());
+        assert_eq!(plan.bridge.program, "node");
+        assert_eq!(tunnel_target_port(&config), crate::config::DEFAULT_BRIDGE_PORT);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn default_mode_runs_the_worker_too() {
+        let (root, config) = project_config("default-mode", None);
+        let plan = build_plan(Some(&config), |_, _| String::new()).unwrap();
+        assert!(plan.wrangler.is_some());
+        assert_eq!(tunnel_target_port(&config), config.worker_port());
+        let bridge_target = Config { tunnel_target: Some("bridge".into()), ..config };
+        assert_eq!(tunnel_target_port(&bridge_target), crate::config::DEFAULT_BRIDGE_PORT);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn bridge_command_override_is_used() {
+        let config = Config {
+            bridge_command: Some("tsx".into()),
+            bridge_args: Some(vec!["src/bridge/http-server.ts".into()]),
+            ..Default::default()
+        };
+        let plan = bridge_plan(std::path::Path::new("/p"), Some(&config)).unwrap();
+        assert_eq!(plan.program, "tsx");
+        assert_eq!(plan.args, vec!["src/bridge/http-server.ts"]);
+        assert_eq!(plan.cwd, PathBuf::from("/p"));
+    }
+
+    #[test]
+    fn default_bridge_runs_built_output() {
+        let plan = bridge_plan(std::path::Path::new("/p"), None).unwrap();
+        assert_eq!(plan.program, "node");
+        assert_eq!(plan.args, vec!["dist/bridge/http-server.js"]);
+        assert_eq!(plan.env, vec![("PORT".to_string(), "3000".to_string())]);
+    }
+
+    #[test]
+    fn launch_commands_cover_every_process_with_its_environment() {
+        let (root, config) = project_config("launch-commands", None);
+        let commands = launch_commands_for(&config).unwrap();
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["wrangler", "bridge", "tunnel"]);
+        
+        let bridge = &commands[1];
+        assert_eq!(bridge.args, vec!["dist/bridge/http-server.js"]);
+        assert_eq!(bridge.env[0].0, "PATH");
+        assert!(bridge.env.contains(&("PORT".to_string(), "3000".to_string())));
+        assert!(bridge.shell.starts_with("cd "));
+        assert!(bridge.shell.ends_with("dist/bridge/http-server.js"));
+        
+        let tunnel = &commands[2];
+        assert_eq!(tunnel.args, vec!["tunnel", "--url", "http://localhost:8787"]);
+        assert_eq!(tunnel.cwd, None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn launch_commands_hide_secret_env_and_quote_for_sh() {
+        let command = launch_command(
+            "bridge",
+            "definitely-not-installed",
+            vec!["--label".into(), "it's here".into()],
+            Some(PathBuf::from("/My Projects/system")),
+            vec![("BRIDGE_AUTH_TOKEN".into(), "0123456789abcdef0123".into()), ("PORT".into(), "3000".into())],
+            &Config::default(),
+        );
+        assert_eq!(command.env[0].1, "0123456...0123");
+        assert_eq!(command.env[1].1, "3000");
+        assert_eq!(
+            command.shell,
+            "cd '/My Projects/system' && BRIDGE_AUTH_TOKEN=0123456...0123 PORT=3000 definitely-not-installed --label 'it'\\''s here'"
+        );
+    }
+
+    #[test]
+    fn custom_bridge_port_reaches_the_bridge_and_the_worker() {
+        let (root, config) = project_config("bridge-port", None);
+        let config = Config { bridge_port: Some(4100), anthropic_key: Some("sk-ant-test".into()), ..config };
+        let plan = build_plan(Some(&config), |key, port| dev_vars_content(key, port, "secret")).unwrap();
+        assert_eq!(plan.bridge.env, vec![("PORT".to_string(), "4100".to_string())]);
+        assert!(plan.dev_vars.as_deref().unwrap().contains("BRIDGE_URL=http://localhost:4100\n"));
+        assert!(plan.wrangler.as_ref().unwrap().env.is_empty());
+        assert_eq!(required_ports(&config), vec![4100, config.worker_port()]);
+        
+        let clash = Config { bridge_port: Some(config.worker_port()), ..config };
+        assert!(build_plan(Some(&clash), |_, _| String::new()).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn empty_bridge_command_is_rejected() {
+        let config = Config { bridge_command: Some("  ".into()), ..Default::default() };
+        assert!(bridge_plan(std::path::Path::new("/p"), Some(&config)).is_err());
+    }
+
+    #[test]
+    fn finds_node_under_version_managers() {
+        let home = temp_tree("node-versions");
+        for bin in [".nvm/versions/node/v20.11.0/bin", ".nvm/versions/node/v18.19.0/bin", ".volta/tools/image/node/21.0.0/bin"] {
+            std::fs::create_dir_all(home.join(bin)).unwrap();
+            std::fs::write(home.join(bin).join("node"), "").unwrap();
+        }
+        // A version dir without a node binary isn't a candidate
+        std::fs::create_dir_all(home.join(".nvm/versions/node/v16.0.0/bin")).unwrap();
+        
+        let found: Vec<PathBuf> = node_candidates(&home)
+            .into_iter()
+            .filter(|node| node.starts_with(&home))
+            .collect();
+        assert_eq!(found, vec![
+            home.join(".nvm/versions/node/v18.19.0/bin/node"),
+            home.join(".nvm/versions/node/v20.11.0/bin/node"),
+            home.join(".volta/tools/image/node/21.0.0/bin/node"),
+        ]);
+        let _ = std::fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn not_found_message_lists_everything_probed() {
+        let home = PathBuf::from("/Users/me");
+        let message = project_not_found_message(Some(&home), 3);
+        for probed in ["~/Desktop/cua", "~/Desktop/system", "~/Projects/system", "~/code/system"] {
+            assert!(message.contains(probed), "{} missing from {}", probed, message);
+        }
+        assert!(message.contains("scanned ~/Desktop, ~/Projects, ~/dev, ~ up to 3 levels deep"));
+    }
+
+    #[test]
+    fn not_found_message_without_config_or_home() {
+        let message = project_not_found_message(Some(std::path::Path::new("/Users/me")), 2);
+        assert!(message.contains("no projectRoot is configured"));
+        assert!(message.contains("up to 2 levels deep"));
+        
+        let message = project_not_found_message(None, 3);
+        assert!(message.contains("home directory is unknown"));
+    }
+
+    #[test]
+    fn reads_hostname_from_ingress_rules() {
+        let config = "tunnel: 6ff42ae2-765d-4adf-8112-31c55c1551ef\n\
+                      credentials-file: /Users/me/.cloudflared/6ff42ae2.json\n\
+                      ingress:\n  \
+                        - hostname: \"mac.example.com\"\n    \
+                          service: http://localhost:8787\n  \
+                        - service: http_status:404\n";
+        assert_eq!(ingress_hostname(config), Some("mac.example.com".to_string()));
+    }
+
+    #[test]
+    fn config_without_hostname_has_no_url() {
+        assert_eq!(ingress_hostname("tunnel: abc\ningress:\n  - service: http_status:404\n"), None);
+        assert!(named_tunnel("/definitely/not/a/cloudflared.yml").is_err());
+    }
+
+    #[test]
+    fn detects_rate_limited_quick_tunnel() {
+        let line = "2024-01-01T00:00:00Z ERR Error unmarshaling QuickTunnel response: error=\"failed to request quick Tunnel: 429 Too Many Requests\"";
+        assert!(is_rate_limited(line));
+        assert!(is_rate_limited("\x1b[31mERR\x1b[0m failed to request quick Tunnel: status code 429"));
+        assert!(!is_rate_limited("2024-01-01T00:00:00Z INF Requesting new quick Tunnel on trycloudflare.com..."));
+    }
+
+    #[test]
+    fn detects_registered_connection() {
+        assert!(is_tunnel_connected("2024-01-01T00:00:00Z INF Registered tunnel connection connIndex=0 connection=abc location=lhr01"));
+        assert!(!is_tunnel_connected("2024-01-01T00:00:00Z INF Starting tunnel tunnelID=abc"));
+    }
+
+    #[test]
+    fn recorded_process_must_still_run_the_same_command() {
+        let record = RuntimeRecord {
+            kind: ProcessKind::Bridge,
+            pid: 4242,
+            command: "node dist/bridge/http-server.js".to_string(),
+        };
+        assert!(still_ours(&record, Some("node dist/bridge/http-server.js")));
+        // The PID was reused by something else after our process died
+        assert!(!still_ours(&record, Some("/usr/libexec/some-daemon")));
+        assert!(!still_ours(&record, None));
+    }
+
+    #[test]
+    fn runtime_records_round_trip() {
+        let records = vec![RuntimeRecord {
+            kind: ProcessKind::LocalServer,
+            pid: 100,
+            command: "npx wrangler dev --ip 127.0.0.1 --port 8787".to_string(),
+        }];
+        let json = serde_json::to_string(&records).unwrap();
+        assert!(json.contains("\"kind\":\"localServer\""));
+        assert_eq!(serde_json::from_str::<Vec<RuntimeRecord>>(&json).unwrap(), records);
+    }
+
+    #[test]
+    fn log_source_names_round_trip() {
+        for kind in ProcessKind::ALL {
+            assert_eq!(ProcessKind::from_log_name(kind.log_name()), Some(kind));
+        }
+        assert_eq!(ProcessKind::from_log_name("system"), None);
+    }
+
+    #[test]
+    fn reads_only_new_complete_lines() {
+        let dir = temp_tree("follow");
+        let path = dir.join("bridge.log");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut offset = std::fs::metadata(&path).unwrap().len();
+        
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "one\ntw").unwrap();
+        assert_eq!(read_new_lines(&path, &mut offset), vec!["one"]);
+        writeln!(file, "o").unwrap();
+        assert_eq!(read_new_lines(&path, &mut offset), vec!["two"]);
+        assert!(read_new_lines(&path, &mut offset).is_empty());
+        
+        // A respawn truncates the log; pick up from the start of the new one
+        std::fs::write(&path, "fresh\n").unwrap();
+        assert_eq!(read_new_lines(&path, &mut offset), vec!["fresh"]);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_whoami_table() {
+        let output = "👋 You are logged in with an OAuth Token, associated with the email me@example.com.\n\
+┌──────────────┬──────────────────────────────────┐\n\
+│ Account Name │ Account ID                       │\n\
+├──────────────┼──────────────────────────────────┤\n\
+│ Personal     │ 0123456789abcdef0123456789abcdef │\n\
+│ Work         │ fedcba9876543210fedcba9876543210 │\n\
+└──────────────┴──────────────────────────────────┘";
+        let (email, accounts) = parse_whoami(output);
+        assert_eq!(email.as_deref(), Some("me@example.com"));
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0], CloudflareAccount { name: "Personal".into(), id: "0123456789abcdef0123456789abcdef".into() });
+    }
+
+    #[test]
+    fn matches_only_our_command_lines() {
+        assert!(is_our_command("node /usr/local/bin/wrangler dev --port 8787", 8787, 3000));
+        assert!(is_our_command("node dist/bridge/http-server.js", 8787, 3000));
+        assert!(is_our_command("cloudflared tunnel --url http://localhost:8787", 8787, 3000));
+        assert!(is_our_command("cloudflared tunnel --url http://192.168.1.20:8787", 8787, 3000));
+        
+        assert!(!is_our_command("node /usr/local/bin/wrangler dev --port 9000", 8787, 3000));
+        assert!(!is_our_command("node server.js", 8787, 3000));
+        assert!(!is_our_command("cloudflared tunnel run my-tunnel", 8787, 3000));
+    }
+
+    #[test]
+    fn formats_timestamps_as_rfc3339() {
+        let time = std::time::UNIX_EPOCH + Duration::from_millis(1_709_251_200_123);
+        assert_eq!(format_timestamp(time), "2024-03-01T00:00:00.123Z");
+    }
+
+    #[test]
+    fn json_log_record_has_expected_shape() {
+        let line = format_log_record(LogFormat::Json, "2024-03-01T00:00:00.123Z", "warn", "tunnel", "exited \"badly\"");
+        let record: serde_json::Value = serde_json::from_str(&line).expect("record is valid JSON");
+        
+        assert_eq!(record, serde_json::json!({
+            "ts": "2024-03-01T00:00:00.123Z",
+            "level": "warn",
+            "source": "tunnel",
+            "msg": "exited \"badly\"",
+        }));
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn strips_ansi_sequences() {
+        assert_eq!(strip_ansi("\u{1b}[1;31mred\u{1b}[0m plain"), "red plain");
+    }
+
+    #[test]
+    fn auth_walls_and_missing_routes_still_count_as_up() {
+        assert!(status_means_up(200));
+        assert!(status_means_up(302));
+        assert!(status_means_up(401));
+        assert!(status_means_up(404));
+        assert!(!status_means_up(0));
+        assert!(!status_means_up(500));
+        assert!(!status_means_up(530));
+    }
+
+    fn sleeper() -> Child {
+        Command::new("sleep").arg("30").process_group(0).spawn().unwrap()
+    }
+
+    #[test]
+    fn registered_process_is_tracked_until_taken() {
+        let registry = ProcessRegistry::default();
+        registry.register(ProcessKind::Bridge, sleeper(), "sleep 30".into());
+        assert!(registry.is_running(ProcessKind::Bridge));
+        assert!(!registry.is_running(ProcessKind::Tunnel));
+        assert_eq!(registry.command(ProcessKind::Bridge).as_deref(), Some("sleep 30"));
+        assert_eq!(registry.pids().len(), 1);
+        
+        let mut child = registry.take(ProcessKind::Bridge).unwrap();
+        assert!(registry.pid(ProcessKind::Bridge).is_none());
+        assert!(registry.take(ProcessKind::Bridge).is_none());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn stop_ends_the_process_group_and_records_its_exit() {
+        // A shell with a grandchild, like npx leaving workerd behind
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & echo $!; wait"])
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+        let grandchild: u32 = line.trim().parse().unwrap();
+        let registry = ProcessRegistry::default();
+        registry.register(ProcessKind::Tunnel, child, "sh -c 'sleep 30 & wait'".into());
+        
+        assert!(registry.stop(ProcessKind::Tunnel));
+        assert!(!registry.is_running(ProcessKind::Tunnel));
+        assert_eq!(registry.last_exit(ProcessKind::Tunnel).and_then(|s| s.signal()), Some(15));
+        
+        // The grandchild went with the group, though whoever inherited it may not have reaped it yet
+        let mut dead = false;
+        for _ in 0..50 {
+            let state = Command::new("ps").args(["-o", "stat=", "-p", &grandchild.to_string()]).output().unwrap();
+            let state = String::from_utf8_lossy(&state.stdout).trim().to_string();
+            dead = state.is_empty() || state.starts_with('Z');
+            if dead {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(dead, "grandchild {} outlived stop", grandchild);
+        
+        // Nothing left to stop
+        assert!(!registry.stop(ProcessKind::Tunnel));
+    }
+
+    #[test]
+    fn exited_process_is_reaped_once() {
+        let registry = ProcessRegistry::default();
+        registry.register(ProcessKind::LocalServer, Command::new("true").spawn().unwrap(), "true".into());
+        for _ in 0..100 {
+            if !registry.is_running(ProcessKind::LocalServer) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        
+        assert!(registry.reap(ProcessKind::LocalServer).unwrap().success());
+        assert!(registry.reap(ProcessKind::LocalServer).is_none());
+        assert!(registry.last_exit(ProcessKind::LocalServer).unwrap().success());
+        assert!(registry.pid(ProcessKind::LocalServer).is_none());
+    }
+
+    #[test]
+    fn bound_port_is_reported_taken_until_released() {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        
+        let taken = check_port(port);
+        assert!(!taken.free);
+        // lsof isn't everywhere, but when it answers it should name this process
+        if let Some(pid) = taken.holder_pid {
+            assert_eq!(pid, std::process::id());
+        }
+        
+        drop(listener);
+        let released = check_port(port);
+        assert!(released.free);
+        assert!(released.holder.is_none());
+    }
+
+    #[test]
+    fn local_only_needs_just_the_bridge_port() {
+        let local_only = Config { mode: Some(crate::config::LOCAL_ONLY_MODE.into()), ..Default::default() };
+        assert_eq!(required_ports(&local_only), vec![crate::config::DEFAULT_BRIDGE_PORT]);
+        let custom = Config { worker_port: Some(9000), ..Default::default() };
+        assert_eq!(required_ports(&custom), vec![crate::config::DEFAULT_BRIDGE_PORT, 9000]);
+    }
+
+    #[test]
+    fn matching_bridge_url_is_in_sync() {
+        let check = compare_deployment(200, r#"{"version":"1.0.0","bridgeUrl":"https://a.trycloudflare.com/"}"#, Some("https://a.trycloudflare.com"));
+        assert!(check.in_sync);
+        assert!(check.differences.is_empty());
+    }
+
+    #[test]
+    fn rotated_tunnel_url_is_out_of_sync() {
+        let check = compare_deployment(200, r#"{"bridgeUrl":"https://old.trycloudflare.com"}"#, Some("https://new.trycloudflare.com"));
+        assert!(check.checked);
+        assert!(!check.in_sync);
+        assert!(check.differences[0].contains("old.trycloudflare.com"));
+        assert!(check.suggestion.is_some());
+    }
+
+    #[test]
+    fn rejected_secret_is_a_difference() {
+        let check = compare_deployment(401, r#"{"error":"Unauthorized"}"#, Some("https://a.trycloudflare.com"));
+        assert!(check.checked);
+        assert!(check.differences[0].contains("API_SECRET"));
+    }
+
+    #[test]
+    fn worker_without_status_endpoint_is_unchecked() {
+        let check = compare_deployment(404, "Not found", Some("https://a.trycloudflare.com"));
+        assert!(!check.checked);
+        assert!(!check.in_sync);
+        assert!(check.suggestion.unwrap().contains("Redeploy"));
+    }
+
+    #[test]
+    fn respawns_count_as_restarts_until_reset() {
+        let registry = ProcessRegistry::default();
+        registry.register(ProcessKind::Bridge, sleeper(), "sleep 30".into());
+        let started = registry.started_at().unwrap();
+        assert_eq!(registry.restarts(ProcessKind::Bridge), 0);
+        
+        registry.stop(ProcessKind::Bridge);
+        registry.register(ProcessKind::Bridge, sleeper(), "sleep 30".into());
+        assert_eq!(registry.restarts(ProcessKind::Bridge), 1);
+        assert_eq!(registry.started_at(), Some(started));
+        
+        registry.reset_stats();
+        assert_eq!(registry.restarts(ProcessKind::Bridge), 0);
+        assert_eq!(registry.started_at(), None);
+        registry.stop(ProcessKind::Bridge);
+    }
+
+    #[test]
+    fn boot_estimate_moves_toward_new_observations() {
+        assert_eq!(update_boot_estimate(None, 4000), 4000);
+        assert_eq!(update_boot_estimate(Some(4000), 2000), 3400);
+        assert_eq!(update_boot_estimate(Some(4000), 14000), 7000);
+        // A pathological boot is capped before it skews the estimate
+        assert_eq!(update_boot_estimate(None, 600_000), 90_000);
+    }
+
+    #[test]
+    fn boot_timing_stays_within_bounds() {
+        assert_eq!(BootTiming::from_estimate(None).timeout, WORKER_READY_TIMEOUT);
+        let fast = BootTiming::from_estimate(Some(800));
+        assert_eq!(fast.first_check, Duration::from_millis(400));
+        assert_eq!(fast.interval, Duration::from_millis(100));
+        assert_eq!(fast.timeout, WORKER_READY_TIMEOUT_MIN);
+        let slow = BootTiming::from_estimate(Some(60_000));
+        assert_eq!(slow.first_check, WORKER_READY_TIMEOUT_MIN);
+        assert_eq!(slow.interval, Duration::from_millis(500));
+        assert_eq!(slow.timeout, WORKER_READY_TIMEOUT_MAX);
+    }
+
+    #[test]
+    fn secret_files_are_checked_against_the_session() {
+        let root = temp_tree("secret-files");
+        let [dev_vars, bridge_config] = project_secret_files(&root);
+        std::fs::create_dir_all(dev_vars.parent().unwrap()).unwrap();
+        std::fs::write(&dev_vars, dev_vars_content("sk-ant-api03-abcdefghijkl", crate::config::DEFAULT_BRIDGE_PORT, "old-secret-0123456789")).unwrap();
+        std::fs::write(&bridge_config, r#"{"authToken": "new-secret-0123456789"}"#).unwrap();
+        
+        let config = Config::default();
+        let report = inspect_secret_file(&root, dev_vars, Some("new-secret-0123456789"), &config);
+        assert!(report.exists);
+        assert!(report.modified.is_some());
+        assert_eq!(report.matches_session, Some(false));
+        assert!(report.entries.contains(&"ANTHROPIC_API_KEY=sk-ant-...ijkl".to_string()));
+        assert!(!report.entries.iter().any(|e| e.contains("old-secret-0123456789")));
+        assert_eq!(report.warnings.len(), 1);
+        
+        let report = inspect_secret_file(&root, bridge_config, Some("new-secret-0123456789"), &config);
+        assert_eq!(report.matches_session, Some(true));
+        assert_eq!(report.entries, vec!["authToken=new-sec...6789".to_string()]);
+        
+        let report = inspect_secret_file(&root, root.join("missing.json"), None, &config);
+        assert!(!report.exists);
+        assert_eq!(report.matches_session, None);
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn complete_project_is_compatible() {
+        let root = temp_tree("compatible");
+        write_project_markers(&root, "1.2.0");
+        assert!(check_project_compatibility(&root).is_ok());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn old_layout_names_the_missing_files() {
+        let root = temp_tree("old-layout");
+        write_project_markers(&root, "1.0.0");
+        std::fs::remove_file(root.join("src/bridge/http-server.ts")).unwrap();
+        let err = check_project_compatibility(&root).unwrap_err();
+        assert!(matches!(err, AppError::ProjectIncompatible { ref missing, .. } if missing == &["src/bridge/http-server.ts"]));
+        assert!(err.to_string().contains("git pull"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn old_version_is_incompatible() {
+        let root = temp_tree("old-version");
+        write_project_markers(&root, "0.9.3");
+        let err = check_project_compatibility(&root).unwrap_err();
+        assert!(matches!(err, AppError::ProjectIncompatible { ref found_version, .. } if found_version.as_deref() == Some("0.9.3")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(version_at_least("1.10.0", "1.9.0"));
+        assert!(version_at_least("1.0", "1.0.0"));
+        assert!(version_at_least("v2.0.0", "1.0.0"));
+        assert!(!version_at_least("0.9.9", "1.0.0"));
+    }
+
+    #[test]
+    fn configured_timing_overrides_the_estimate() {
+        let config = Config {
+            worker_boot_ms: Some(2_000),
+            timeouts: Some(crate::config::Timeouts {
+                readiness_timeout_secs: Some(300),
+                poll_interval_ms: Some(1_000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let timing = BootTiming::for_config(Some(&config));
+        assert_eq!(timing.timeout, Duration::from_secs(300));
+        assert_eq!(timing.interval, Duration::from_secs(1));
+        assert_eq!(timing.first_check, Duration::from_secs(1));
+        assert_eq!(BootTiming::for_config(None), BootTiming::from_estimate(None));
+    }
+
+    #[test]
+    fn tool_versions_are_parsed_from_cli_output() {
+        assert_eq!(parse_tool_version("cloudflared version 2024.8.2 (built 2024-08-08-1522 UTC)").as_deref(), Some("2024.8.2"));
+        assert_eq!(parse_tool_version(" ⛅️ wrangler 3.78.2\n-------------------").as_deref(), Some("3.78.2"));
+        assert_eq!(parse_tool_version("3.114.0").as_deref(), Some("3.114.0"));
+        assert_eq!(parse_tool_version("cloudflared version DEV"), None);
+    }
+
+    #[test]
+    fn old_and_unreadable_tool_versions_are_flagged() {
+        let old = assess_tool_version("cloudflared", "2024.1.0", "upgrade", Some("cloudflared version 2023.5.0 (built ...)"));
+        assert_eq!(old.up_to_date, Some(false));
+        assert_eq!(old.hint.as_deref(), Some("upgrade"));
+        
+        let current = assess_tool_version("wrangler", "3.0.0", "upgrade", Some("3.78.2"));
+        assert_eq!(current.up_to_date, Some(true));
+        assert!(current.hint.is_none());
+        
+        let odd = assess_tool_version("cloudflared", "2024.1.0", "upgrade", Some("cloudflared version DEV"));
+        assert_eq!(odd.up_to_date, None);
+        assert_eq!(odd.raw.as_deref(), Some("cloudflared version DEV"));
+        
+        let missing = assess_tool_version("wrangler", "3.0.0", "upgrade", None);
+        assert!(missing.version.is_none());
+        assert!(missing.hint.unwrap().contains("isn't installed"));
+    }
+
+    #[test]
+    fn provider_defaults_to_cloudflared() {
+        let provider = tunnel_provider(&Config::default()).unwrap();
+        assert_eq!(provider.program(), "cloudflared");
+        assert_eq!(
+            provider.command_line("127.0.0.1".parse().unwrap(), 8787),
+            "cloudflared tunnel --url http://localhost:8787"
+        );
+        let bore = Config { tunnel_provider: Some("bore".into()), ..Default::default() };
+        assert_eq!(tunnel_provider(&bore).unwrap().program(), "bore");
+        let unknown = Config { tunnel_provider: Some("ngrock".into()), ..Default::default() };
+        assert!(tunnel_provider(&unknown).is_err());
+    }
+
+    #[test]
+    fn providers_find_their_urls() {
+        let quick = Cloudflared { named: None };
+        assert_eq!(
+            quick.url("INF |  https://abc-def.trycloudflare.com  |").as_deref(),
+            Some("https://abc-def.trycloudflare.com")
+        );
+        assert!(quick.failure("ERR failed to request quick Tunnel: 429 Too Many Requests").is_some());
+        
+        let named = Cloudflared { named: Some((PathBuf::from("/c.yml"), "https://mac.example.com".into())) };
+        assert_eq!(named.url("INF Registered tunnel connection connIndex=0").as_deref(), Some("https://mac.example.com"));
+        assert_eq!(named.url("INF Starting tunnel"), None);
+        
+        let bore = Bore { server: DEFAULT_BORE_SERVER.into() };
+        assert_eq!(
+            bore.url("2024-01-01T00:00:00Z \u{1b}[32m INFO\u{1b}[0m bore_cli::client: listening at bore.pub:41234").as_deref(),
+            Some("http://bore.pub:41234")
+        );
+        assert_eq!(bore.url("INFO bore_cli::client: connected to server remote_port=41234"), None);
+        assert_eq!(
+            bore.command_line("0.0.0.0".parse().unwrap(), 3000),
+            "bore local 3000 --local-host 127.0.0.1 --to bore.pub"
+        );
+    }
+
+    #[test]
+    fn auth_failures_are_told_apart_by_payload() {
+        let anthropic = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        assert_eq!(classify_auth_failure(401, anthropic), Some(AuthFailure::AnthropicKey));
+        assert_eq!(
+            classify_auth_failure(403, r#"{"type":"error","error":{"type":"permission_error"}}"#),
+            Some(AuthFailure::AnthropicKey)
+        );
+        assert_eq!(classify_auth_failure(401, r#"{"error":"Unauthorized"}"#), Some(AuthFailure::ApiSecret));
+        assert_eq!(classify_auth_failure(200, anthropic), None);
+        assert_eq!(classify_auth_failure(0, ""), None);
+    }
+
+    #[test]
+    fn persistent_auth_failures_are_reported_once() {
+        let mut watch = AuthWatch::default();
+        assert_eq!(watch.update(Some(AuthFailure::AnthropicKey)), None);
+        assert_eq!(watch.update(Some(AuthFailure::AnthropicKey)), Some(AuthFailure::AnthropicKey));
+        assert_eq!(watch.update(Some(AuthFailure::AnthropicKey)), None);
+        // A blip of success resets the streak
+        assert_eq!(watch.update(None), None);
+        assert_eq!(watch.update(Some(AuthFailure::ApiSecret)), None);
+        assert_eq!(watch.update(Some(AuthFailure::ApiSecret)), Some(AuthFailure::ApiSecret));
+    }
+
+    #[test]
+    fn concurrent_starts_spawn_one_process() {
+        let flags = StartFlags::default();
+        let registry = ProcessRegistry::default();
+        let barrier = std::sync::Barrier::new(2);
+        let outcomes: Vec<bool> = thread::scope(|scope| {
+            let start = || {
+                barrier.wait();
+                let Ok(_starting) = flags.claim_idle(&registry, ProcessKind::Tunnel) else {
+                    return false;
+                };
+                // Spawning takes a while, which is the window a duplicate used to slip through
+                thread::sleep(Duration::from_millis(50));
+                registry.register(ProcessKind::Tunnel, sleeper(), "sleep 30".into());
+                true
+            };
+            let handles = [scope.spawn(start), scope.spawn(start)];
+            handles.map(|h| h.join().unwrap()).to_vec()
+        });
+        
+        assert_eq!(outcomes.iter().filter(|spawned| **spawned).count(), 1);
+        assert_eq!(registry.pids().len(), 1);
+        // The flag clears once the start is over, and a later start sees the running process
+        assert!(flags.claim(ProcessKind::Tunnel).is_ok());
+        let refused = flags.claim_idle(&registry, ProcessKind::Tunnel).err().unwrap();
+        assert!(refused.to_string().contains("already running"));
+        registry.stop(ProcessKind::Tunnel);
+    }
+
+    #[test]
+    fn synced_dev_vars_match_the_config() {
+        let (root, config) = project_config("sync-dev-vars", None);
+        let config = Config {
+            anthropic_key: Some("sk-ant-api03-abcdefghijkl".into()),
+            anthropic_base_url: Some("https://gw.example.com".into()),
+            ..config
+        };
+        let secret = "0123456789abcdef0123456789abcdef";
+        
+        let shown = sync_dev_vars_with(&config, secret).unwrap();
+        let written = std::fs::read_to_string(std::fs::canonicalize(&root).unwrap().join("cloudflare-agent/.dev.vars")).unwrap();
+        assert!(written.contains("ANTHROPIC_API_KEY=sk-ant-api03-abcdefghijkl\n"));
+        assert!(written.contains(&format!("API_SECRET={}\n", secret)));
+        assert!(written.contains("ANTHROPIC_BASE_URL=https://gw.example.com\n"));
+        
+        assert!(!shown.contains("sk-ant-api03-abcdefghijkl"));
+        assert!(!shown.contains(secret));
+        assert!(shown.contains("ANTHROPIC_BASE_URL=https://gw.example.com\n"));
+        assert_eq!(shown.lines().count(), written.lines().count());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn syncing_without_a_key_is_refused() {
+        let (root, config) = project_config("sync-no-key", None);
+        let err = sync_dev_vars_with(&config, "secret").unwrap_err();
+        assert!(err.to_string().contains("No Anthropic API key"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn binaries_resolve_to_the_first_executable_on_the_path() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let first = temp_tree("resolve-first");
+        let second = temp_tree("resolve-second");
+        let write = |dir: &std::path::Path, mode: u32| {
+            let bin = dir.join("cloudflared");
+            std::fs::write(&bin, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(mode)).unwrap();
+            bin
+        };
+        // Not executable, so it's skipped the way execvp skips it
+        write(&first, 0o644);
+        let expected = write(&second, 0o755);
+        let path = format!("{}::{}", first.display(), second.display());
+        
+        assert_eq!(resolve_in("cloudflared", &path), Some(expected.clone()));
+        assert_eq!(resolve_in("wrangler", &path), None);
+        // A path is taken as given rather than searched for
+        assert_eq!(resolve_in(&expected.to_string_lossy(), ""), Some(expected.clone()));
+        assert_eq!(resolve_in(&first.join("cloudflared").to_string_lossy(), &path), None);
+        let _ = std::fs::remove_dir_all(&first);
+        let _ = std::fs::remove_dir_all(&second);
+    }
+
+    #[test]
+    fn spawn_failures_say_what_resolved_or_where_we_looked() {
+        let err = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let resolved = spawn_failure("cloudflared", Some(std::path::Path::new("/opt/homebrew/bin/cloudflared")), "/usr/bin", &err);
+        assert!(resolved.starts_with("Resolved cloudflared to /opt/homebrew/bin/cloudflared but spawn failed"));
+        
+        let missing = spawn_failure("cloudflared", None, "/opt/homebrew/bin::/usr/bin", &err);
+        assert_eq!(missing, "cloudflared not found in /opt/homebrew/bin, /usr/bin");
+    }
+
+    #[test]
+    fn secret_list_is_read_past_the_banner() {
+        let output = "\u{1b}[33m ⛅️ wrangler 3.80.0\u{1b}[0m\n-------------------\n[\n  {\n    \"name\": \"API_SECRET\",\n    \"type\": \"secret_text\"\n  },\n  {\n    \"name\": \"MY_TOKEN\",\n    \"type\": \"secret_text\"\n  }\n]\n";
+        assert_eq!(parse_secret_list(output).unwrap(), vec![
+            WorkerSecret { name: "API_SECRET".into(), managed: true },
+            WorkerSecret { name: "MY_TOKEN".into(), managed: false },
+        ]);
+        assert!(parse_secret_list(" ⛅️ wrangler 3.80.0\n[]\n").unwrap().is_empty());
+        assert!(parse_secret_list("✘ [ERROR] You are not authenticated").is_err());
+    }
+
+    #[test]
+    fn only_plain_secret_names_can_be_deleted() {
+        assert!(is_valid_secret_name("BRIDGE_AUTH_TOKEN"));
+        assert!(!is_valid_secret_name(""));
+        assert!(!is_valid_secret_name("--name"));
+        assert!(!is_valid_secret_name("1SECRET"));
+        assert!(!is_valid_secret_name("API SECRET"));
+    }
+
+    #[test]
+    fn unauthenticated_wrangler_says_to_log_in() {
+        let message = describe_wrangler_failure("✘ [ERROR] You are not authenticated. Please run `wrangler login`.");
+        assert!(message.contains("wrangler login"));
+    }
+
+    #[test]
+    fn server_counts_as_running_only_when_the_mode_has_everything_up() {
+        let registry = ProcessRegistry::default();
+        assert!(!registry.server_running(false));
+        assert!(!registry.server_running(true));
+        
+        registry.register(ProcessKind::Bridge, sleeper(), "sleep 30".into());
+        assert!(!registry.server_running(false));
+        assert!(registry.server_running(true));
+        
+        registry.register(ProcessKind::LocalServer, sleeper(), "sleep 30".into());
+        assert!(registry.server_running(false));
+        
+        registry.stop(ProcessKind::Bridge);
+        assert!(!registry.server_running(false));
+        registry.stop(ProcessKind::LocalServer);
+    }
+
+    #[test]
+    fn latency_stats_skip_failed_samples() {
+        let stats = latency_stats("http://localhost:8787/", &[Some(12.0), None, Some(3.5), Some(40.0), Some(8.0)]);
+        assert_eq!((stats.ok, stats.failed), (4, 1));
+        assert_eq!(stats.min_ms, Some(3.5));
+        assert_eq!(stats.median_ms, Some(10.0));
+        assert_eq!(stats.max_ms, Some(40.0));
+        
+        assert_eq!(latency_stats("u", &[Some(5.0), Some(1.0), Some(9.0)]).median_ms, Some(5.0));
+        let down = latency_stats("u", &[None, None]);
+        assert_eq!((down.ok, down.failed, down.median_ms), (0, 2, None));
+    }
+
+    #[tokio::test]
+    async fn port_wait_sees_a_listener_an_exit_or_a_timeout() {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let interval = Duration::from_millis(20);
+        // A stub standing in for the bridge on a port of its own, so a real bridge on 3000 can't interfere
+        let stub = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = stub.local_addr().unwrap().port();
+        assert_eq!(wait_for_port(localhost, port, Duration::from_secs(2), interval, || true).await, Boot::Ready);
+        
+        drop(stub);
+        assert_eq!(wait_for_port(localhost, port, Duration::from_secs(2), interval, || false).await, Boot::Died);
+        assert_eq!(wait_for_port(localhost, port, Duration::from_millis(100), interval, || true).await, Boot::TimedOut);
+    }
+
+    #[test]
+    fn bridge_not_ready_includes_its_output() {
+        let err = AppError::BridgeNotReady { port: 3000, exited: true, output: "Error: listen EADDRINUSE :::3000".into() };
+        let message = err.to_string();
+        assert!(message.starts_with("The bridge exited before it started listening on port 3000"));
+        assert!(message.ends_with("EADDRINUSE :::3000"));
+        let err = AppError::BridgeNotReady { port: 3000, exited: false, output: String::new() };
+        assert_eq!(err.to_string(), "The bridge didn't start listening on port 3000 in time");
+    }
+
+    #[test]
+    fn an_unusable_configured_root_is_reported_not_replaced() {
+        let config = Config { project_root: Some("/Volumes/Unmounted/system".into()), ..Default::default() };
+        let err = find_project_root_with(Some(&config), false).unwrap_err();
+        assert!(matches!(err.downcast_ref::<AppError>(), Some(AppError::ProjectRootInvalid { .. })));
+    }
+
+    #[test]
+    fn only_our_processes_and_their_groups_count_as_managed() {
+        let managed = [4100, 4200];
+        assert!(is_managed(4100, Some(4100), &managed));
+        // node under npx: its own pid, but the group of the process we spawned
+        assert!(is_managed(4137, Some(4100), &managed));
+        assert!(!is_managed(999, Some(999), &managed));
+        assert!(!is_managed(999, None, &managed));
     }
-    Ok(())
 }
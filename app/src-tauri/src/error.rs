@@ -0,0 +1,157 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Errors with a specific cause the UI can explain to the user
+#[derive(Debug)]
+pub enum AppError {
+    /// Nothing is accepting connections on the local worker port
+    LocalServerNotRunning { port: u16 },
+    /// Neither HOME nor the user database gives us a usable home directory
+    HomeDirUnavailable,
+    /// Another start/stop operation is still in flight
+    Busy,
+    /// The user called off an operation before it finished
+    Cancelled,
+    /// cloudflared didn't report a tunnel URL in time
+    TunnelTimeout { secs: u64 },
+    /// Cloudflare is refusing new quick tunnels from this machine for now
+    TunnelRateLimited,
+    /// The bridge's compiled entry point is missing because the TypeScript wasn't built
+    BridgeNotBuilt { entry: PathBuf },
+    /// The bridge exited or never opened its port after being spawned
+    BridgeNotReady { port: u16, exited: bool, output: String },
+    /// We aren't allowed to write a file we need (permissions, read-only volume)
+    NotWritable { path: PathBuf },
+    /// The disk filled up while writing a file
+    DiskFull { path: PathBuf },
+    /// A chosen project folder doesn't exist or isn't the SYSTEM project
+    ProjectRootInvalid { path: PathBuf },
+    /// The folder is the SYSTEM project, but a version this app can't run
+    ProjectIncompatible { found_version: Option<String>, required: String, missing: Vec<String> },
+    /// The configured bind host isn't an IP address
+    InvalidBindHost { value: String },
+    /// The configured Anthropic base URL isn't an https URL
+    InvalidBaseUrl { value: String },
+    /// A permission name the app doesn't know about
+    UnknownPermission { name: String },
+    /// `open` couldn't bring up the System Settings pane for a permission
+    SettingsOpenFailed { permission: String, reason: String },
+    /// Something other than SYSTEM is holding a port we need
+    PortInUse { port: u16, holder: Option<String> },
+    /// Neither Application Support nor the fallback folder can hold the config, e.g. on a managed Mac
+    NoWritableConfigDir { tried: Vec<PathBuf> },
+    /// Writing a file failed for some other reason
+    WriteFailed { path: PathBuf, message: String },
+}
+
+impl AppError {
+    /// Classify an IO error from writing `path` so the message says what to fix
+    pub fn from_write(path: &std::path::Path, err: std::io::Error) -> Self {
+        let path = path.to_path_buf();
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem => AppError::NotWritable { path },
+            std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => AppError::DiskFull { path },
+            _ => AppError::WriteFailed { path, message: err.to_string() },
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::LocalServerNotRunning { port } => write!(
+                f,
+                "Local server is not running on port {} - start it before starting the tunnel",
+                port
+            ),
+            AppError::HomeDirUnavailable => write!(
+                f,
+                "Could not determine your home directory - make sure HOME is set"
+            ),
+            AppError::Busy => write!(f, "Another start or stop is already in progress"),
+            AppError::Cancelled => write!(f, "Cancelled"),
+            AppError::TunnelTimeout { secs } => write!(f, "Timeout waiting for tunnel URL after {}s", secs),
+            AppError::TunnelRateLimited => write!(
+                f,
+                "Cloudflare is rate-limiting quick tunnels right now - wait a few minutes and try again, or set up a named tunnel"
+            ),
+            AppError::BridgeNotBuilt { entry } => write!(
+                f,
+                "The bridge hasn't been built ({} is missing) - run `npm run build` in the project folder",
+                entry.display()
+            ),
+            AppError::BridgeNotReady { port, exited, output } => {
+                if *exited {
+                    write!(f, "The bridge exited before it started listening on port {}", port)?;
+                } else {
+                    write!(f, "The bridge didn't start listening on port {} in time", port)?;
+                }
+                if output.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, ":\n{}", output)
+                }
+            }
+            AppError::NotWritable { path } => write!(
+                f,
+                "Can't write {} - check the folder's permissions and that the disk isn't read-only",
+                path.display()
+            ),
+            AppError::DiskFull { path } => write!(
+                f,
+                "Ran out of disk space writing {} - free up some space and try again",
+                path.display()
+            ),
+            AppError::ProjectRootInvalid { path } => write!(
+                f,
+                "{} isn't the SYSTEM project folder (it should contain cloudflare-agent)",
+                path.display()
+            ),
+            AppError::ProjectIncompatible { missing, .. } if !missing.is_empty() => write!(
+                f,
+                "The SYSTEM project is missing {} - it's probably an old clone; run `git pull` in the project folder",
+                missing.join(", ")
+            ),
+            AppError::ProjectIncompatible { found_version, required, .. } => write!(
+                f,
+                "The SYSTEM project is version {}, but this app needs {} or newer - run `git pull` in the project folder",
+                found_version.as_deref().unwrap_or("unknown"),
+                required
+            ),
+            AppError::InvalidBindHost { value } => write!(
+                f,
+                "bindHost \"{}\" isn't an IP address - use 127.0.0.1, 0.0.0.0, or this Mac's LAN address",
+                value
+            ),
+            AppError::InvalidBaseUrl { value } => write!(
+                f,
+                "anthropicBaseUrl \"{}\" isn't an https URL - use something like https://gateway.example.com",
+                crate::config::redact_url_credentials(value)
+            ),
+            AppError::UnknownPermission { name } => write!(f, "Unknown permission: {}", name),
+            AppError::SettingsOpenFailed { permission, reason } => write!(
+                f,
+                "Couldn't open System Settings for {}: {}",
+                permission, reason
+            ),
+            AppError::PortInUse { port, holder: Some(holder) } => write!(
+                f,
+                "Port {} is in use by `{}` - quit it and try again",
+                port, holder
+            ),
+            AppError::PortInUse { port, holder: None } => write!(
+                f,
+                "Port {} is in use by another process - find it with `lsof -i:{}` and quit it",
+                port, port
+            ),
+            AppError::NoWritableConfigDir { tried } => write!(
+                f,
+                "Can't save settings anywhere - none of {} are writable; check your home folder's permissions or ask your administrator",
+                tried.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            AppError::WriteFailed { path, message } => write!(f, "Failed to write {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
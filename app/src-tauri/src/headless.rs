@@ -0,0 +1,222 @@
+use crate::config::Config;
+use crate::{bridge, config};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Config fields the worker and bridge read at startup; changing one means restarting them
+const SERVER_FIELDS: &[&str] = &[
+    "anthropicKey", "anthropicBaseUrl", "projectRoot", "workerPort", "bridgePort", "bindHost", "bridgeCommand", "bridgeArgs",
+    "nodeBin", "npxBin", "mode", "healthPath",
+];
+
+/// Config fields that decide where the tunnel points
+const TUNNEL_FIELDS: &[&str] = &["workerPort", "bridgePort", "bindHost", "mode", "cloudflaredConfigPath", "tunnelTarget", "tunnelProvider", "boreServer"];
+
+/// What the daemon is currently running with
+struct Session {
+    token: String,
+    config: Config,
+}
+
+/// Run the bridge lifecycle without a window: start everything, print the connection
+/// details, and block until Ctrl-C, then tear it all down
+/// SIGHUP reloads the config and restarts whatever the change affects
+pub fn run() -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start async runtime: {}", e);
+            return 1;
+        }
+    };
+    
+    runtime.block_on(async {
+        let code = match start().await {
+            Ok(mut session) => {
+                println!("Press Ctrl-C to stop, or send SIGHUP to reload the config");
+                match signal(SignalKind::hangup()) {
+                    Ok(mut hangup) => loop {
+                        tokio::select! {
+                            _ = tokio::signal::ctrl_c() => break,
+                            _ = hangup.recv() => reload(&mut session).await,
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Config reload on SIGHUP unavailable: {}", e);
+                        let _ = tokio::signal::ctrl_c().await;
+                    }
+                }
+                println!("Stopping...");
+                0
+            }
+            Err(e) => {
+                eprintln!("Startup failed: {}", e);
+                1
+            }
+        };
+        
+        let _ = bridge::stop_all().await;
+        code
+    })
+}
+
+async fn start() -> Result<Session, Box<dyn std::error::Error + Send + Sync>> {
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    if config.anthropic_key.is_none() {
+        return Err("No Anthropic API key configured - run the app once to set it up".into());
+    }
+    
+    let token = bridge::generate_token();
+    start_server(&token).await?;
+    start_tunnel().await?;
+    
+    println!("API secret: {}", token);
+    Ok(Session { token, config })
+}
+
+async fn start_server(token: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    bridge::start_local_server(token, &|stage, status| {
+        bridge::log("info", "startup", &format!("{}: {}", stage, status));
+    })
+    .await
+}
+
+async fn start_tunnel() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let url = bridge::start_tunnel_and_get_url(|old, new| {
+        bridge::log("info", "tunnel", &format!("URL changed from {} to {}", old, new));
+        save_tunnel_url(&new);
+    })
+    .await?;
+    save_tunnel_url(&url);
+    
+    println!("Tunnel URL: {}", url);
+    Ok(())
+}
+
+/// Re-read the config and restart only the processes whose settings changed
+/// A config that doesn't load, or fails to start, is logged and the daemon carries on
+async fn reload(session: &mut Session) {
+    let new = match config::read_config() {
+        Ok(config) => config,
+        Err(e) => {
+            bridge::log("warn", "system", &format!("SIGHUP: config not reloaded, it doesn't parse: {}", e));
+            return;
+        }
+    };
+    let changed = changed_fields(&session.config, &new);
+    if changed.is_empty() {
+        bridge::log("info", "system", "SIGHUP: config unchanged, nothing restarted");
+        return;
+    }
+    let restart_server = changed.iter().any(|f| SERVER_FIELDS.contains(f));
+    let restart_tunnel = changed.iter().any(|f| TUNNEL_FIELDS.contains(f));
+    bridge::log("info", "system", &format!(
+        "SIGHUP: reloaded config ({} changed), restarting {}",
+        changed.join(", "),
+        match (restart_server, restart_tunnel) {
+            (true, true) => "server and tunnel",
+            (true, false) => "server",
+            (false, true) => "tunnel",
+            (false, false) => "nothing",
+        }
+    ));
+    
+    // The session keeps the old config until the restarts succeed, so the next SIGHUP still sees the
+    // change and tries again
+    if restart_server {
+        if let Err(e) = start_server(&session.token).await {
+            bridge::log("error", "system", &format!("SIGHUP: server restart failed: {}", e));
+            return;
+        }
+    }
+    if restart_tunnel {
+        bridge::stop_async(bridge::ProcessKind::Tunnel).await;
+        if let Err(e) = start_tunnel().await {
+            bridge::log("error", "system", &format!("SIGHUP: tunnel restart failed: {}", e));
+            return;
+        }
+    }
+    session.config = new;
+}
+
+/// Names of the config fields that differ between `old` and `new`
+fn changed_fields(old: &Config, new: &Config) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    let mut check = |name, differs: bool| {
+        if differs {
+            changed.push(name);
+        }
+    };
+    check("anthropicKey", old.anthropic_key != new.anthropic_key);
+    check("anthropicBaseUrl", old.anthropic_base_url().ok().flatten() != new.anthropic_base_url().ok().flatten());
+    check("projectRoot", old.project_root != new.project_root);
+    check("workerPort", old.worker_port() != new.worker_port());
+    check("bridgePort", old.bridge_port() != new.bridge_port());
+    check("bindHost", old.bind_host != new.bind_host);
+    check("bridgeCommand", old.bridge_command != new.bridge_command);
+    check("bridgeArgs", old.bridge_args != new.bridge_args);
+    check("nodeBin", old.node_bin != new.node_bin);
+    check("npxBin", old.npx_bin != new.npx_bin);
+    check("mode", old.mode != new.mode);
+    check("healthPath", old.health_path() != new.health_path());
+    check("cloudflaredConfigPath", old.cloudflared_config_path != new.cloudflared_config_path);
+    check("tunnelTarget", old.tunnel_target() != new.tunnel_target());
+    check("tunnelProvider", old.tunnel_provider != new.tunnel_provider);
+    check("boreServer", old.bore_server != new.bore_server);
+    changed
+}
+
+fn save_tunnel_url(url: &str) {
+    if let Ok(mut config) = config::load_config() {
+        config.tunnel_url = Some(url.to_string());
+        let _ = config::save_config(&config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrelated_fields_are_not_changes() {
+        let old = Config { anthropic_key: Some("sk-ant-a".into()), ..Default::default() };
+        let new = Config { tunnel_url: Some("https://x.trycloudflare.com".into()), metrics_enabled: Some(true), ..old.clone() };
+        assert!(changed_fields(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn key_change_restarts_only_the_server() {
+        let old = Config { anthropic_key: Some("sk-ant-a".into()), ..Default::default() };
+        let new = Config { anthropic_key: Some("sk-ant-b".into()), ..Default::default() };
+        let changed = changed_fields(&old, &new);
+        assert_eq!(changed, vec!["anthropicKey"]);
+        assert!(changed.iter().any(|f| SERVER_FIELDS.contains(f)));
+        assert!(!changed.iter().any(|f| TUNNEL_FIELDS.contains(f)));
+    }
+
+    #[test]
+    fn gateway_change_restarts_the_server() {
+        let old = Config { anthropic_base_url: Some("https://gw.example/anthropic".into()), ..Default::default() };
+        let new = Config { anthropic_base_url: Some("https://gw.example/other".into()), ..Default::default() };
+        assert_eq!(changed_fields(&old, &new), vec!["anthropicBaseUrl"]);
+        assert!(SERVER_FIELDS.contains(&"anthropicBaseUrl"));
+        
+        // A trailing slash reaches the same gateway
+        let same = Config { anthropic_base_url: Some("https://gw.example/anthropic/".into()), ..Default::default() };
+        assert!(changed_fields(&old, &same).is_empty());
+    }
+
+    #[test]
+    fn port_change_restarts_both() {
+        let old = Config::default();
+        let new = Config { worker_port: Some(9000), ..Default::default() };
+        let changed = changed_fields(&old, &new);
+        assert!(changed.iter().any(|f| SERVER_FIELDS.contains(f)));
+        assert!(changed.iter().any(|f| TUNNEL_FIELDS.contains(f)));
+    }
+
+    #[test]
+    fn spelling_out_the_default_port_is_not_a_change() {
+        let new = Config { worker_port: Some(config::DEFAULT_WORKER_PORT), ..Default::default() };
+        assert!(changed_fields(&Config::default(), &new).is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::{Mutex, MutexGuard};
+
+/// Locking that survives a thread panicking while it held the lock
+pub trait LockExt<T> {
+    /// Lock the mutex, taking the data back from a poisoned lock instead of panicking
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            crate::bridge::log("warn", "system", "Recovered a lock left poisoned by a panicked thread");
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_data_from_a_poisoned_lock() {
+        let mutex = std::sync::Arc::new(Mutex::new(41));
+        let poisoner = std::sync::Arc::clone(&mutex);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+        
+        *mutex.lock_or_recover() += 1;
+        assert_eq!(*mutex.lock_or_recover(), 42);
+        assert!(!mutex.is_poisoned());
+    }
+}
@@ -0,0 +1,181 @@
+use serde::Serialize;
+
+/// Where the app stands as a login item, per SMAppService
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LoginItemStatus {
+    Enabled,
+    Disabled,
+    /// Registered, but the user still has to allow it in System Settings > General > Login Items
+    PendingApproval,
+    /// The system can't find the app bundle to register (e.g. running from a build directory)
+    NotFound,
+    /// Older macOS without SMAppService, or not macOS at all
+    Unsupported,
+}
+
+/// Map an `SMAppServiceStatus` value onto our status
+fn status_from_raw(raw: isize) -> LoginItemStatus {
+    match raw {
+        0 => LoginItemStatus::Disabled,
+        1 => LoginItemStatus::Enabled,
+        2 => LoginItemStatus::PendingApproval,
+        3 => LoginItemStatus::NotFound,
+        _ => LoginItemStatus::Unsupported,
+    }
+}
+
+/// Register or unregister through `change`, then report where that left things
+fn set_enabled_with(
+    enabled: bool,
+    status: impl Fn() -> Option<isize>,
+    change: impl FnOnce(bool) -> Result<(), String>,
+) -> Result<LoginItemStatus, String> {
+    let Some(current) = status().map(status_from_raw) else {
+        return Ok(LoginItemStatus::Unsupported);
+    };
+    let settled = if enabled {
+        matches!(current, LoginItemStatus::Enabled | LoginItemStatus::PendingApproval)
+    } else {
+        matches!(current, LoginItemStatus::Disabled | LoginItemStatus::NotFound)
+    };
+    if settled {
+        return Ok(current);
+    }
+    
+    let result = change(enabled);
+    let after = status().map(status_from_raw).unwrap_or(LoginItemStatus::Unsupported);
+    match result {
+        Ok(()) => Ok(after),
+        // Registering fails when approval is still needed, but the item is in place
+        Err(_) if enabled && after == LoginItemStatus::PendingApproval => Ok(after),
+        Err(e) => Err(format!("Couldn't {} the login item: {}", if enabled { "register" } else { "unregister" }, e)),
+    }
+}
+
+/// Whether the app is registered to run at login
+pub fn status() -> LoginItemStatus {
+    ffi::status().map(status_from_raw).unwrap_or(LoginItemStatus::Unsupported)
+}
+
+/// Register or unregister the app as a login item, returning the resulting status
+pub fn set_enabled(enabled: bool) -> Result<LoginItemStatus, String> {
+    set_enabled_with(enabled, ffi::status, ffi::change)
+}
+
+/// SMAppService.mainApp through the Objective-C runtime
+#[cfg(target_os = "macos")]
+mod ffi {
+    use objc::rc::autoreleasepool;
+    use objc::runtime::{Class, Object, BOOL, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::{c_char, CStr};
+    
+    #[link(name = "ServiceManagement", kind = "framework")]
+    extern "C" {}
+    
+    /// `[SMAppService mainApp]`, or None before macOS 13
+    fn main_app() -> Option<*mut Object> {
+        let class = Class::get("SMAppService")?;
+        let service: *mut Object = unsafe { msg_send![class, mainApp] };
+        (!service.is_null()).then_some(service)
+    }
+    
+    /// The raw `SMAppServiceStatus`, or None when SMAppService isn't available
+    pub fn status() -> Option<isize> {
+        autoreleasepool(|| {
+            let service = main_app()?;
+            Some(unsafe { msg_send![service, status] })
+        })
+    }
+    
+    /// `registerAndReturnError:` or `unregisterAndReturnError:`, with the NSError's description on failure
+    pub fn change(enabled: bool) -> Result<(), String> {
+        autoreleasepool(|| {
+            let service = main_app().ok_or("Login items need macOS 13 or later")?;
+            let mut error: *mut Object = std::ptr::null_mut();
+            let ok: BOOL = unsafe {
+                if enabled {
+                    msg_send![service, registerAndReturnError: &mut error]
+                } else {
+                    msg_send![service, unregisterAndReturnError: &mut error]
+                }
+            };
+            if ok == YES {
+                return Ok(());
+            }
+            Err(describe_error(error))
+        })
+    }
+    
+    /// `[[error localizedDescription] UTF8String]`
+    fn describe_error(error: *mut Object) -> String {
+        if error.is_null() {
+            return "unknown error".to_string();
+        }
+        let text: *mut Object = unsafe { msg_send![error, localizedDescription] };
+        if text.is_null() {
+            return "unknown error".to_string();
+        }
+        let chars: *const c_char = unsafe { msg_send![text, UTF8String] };
+        if chars.is_null() {
+            return "unknown error".to_string();
+        }
+        unsafe { CStr::from_ptr(chars) }.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod ffi {
+    pub fn status() -> Option<isize> {
+        None
+    }
+
+    pub fn change(_enabled: bool) -> Result<(), String> {
+        Err("Login items are only supported on macOS".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn raw_statuses_map_onto_ours() {
+        assert_eq!(status_from_raw(0), LoginItemStatus::Disabled);
+        assert_eq!(status_from_raw(1), LoginItemStatus::Enabled);
+        assert_eq!(status_from_raw(2), LoginItemStatus::PendingApproval);
+        assert_eq!(status_from_raw(3), LoginItemStatus::NotFound);
+        assert_eq!(status_from_raw(7), LoginItemStatus::Unsupported);
+    }
+
+    #[test]
+    fn enabling_reports_what_the_system_settled_on() {
+        let raw = Cell::new(0);
+        let registered = set_enabled_with(true, || Some(raw.get()), |_| {
+            raw.set(1);
+            Ok(())
+        });
+        assert_eq!(registered, Ok(LoginItemStatus::Enabled));
+        
+        // A failed register that leaves the item awaiting approval isn't an error
+        let raw = Cell::new(0);
+        let pending = set_enabled_with(true, || Some(raw.get()), |_| {
+            raw.set(2);
+            Err("Operation not permitted".into())
+        });
+        assert_eq!(pending, Ok(LoginItemStatus::PendingApproval));
+        
+        let failed = set_enabled_with(true, || Some(0), |_| Err("Invalid code signature".into()));
+        assert!(failed.unwrap_err().contains("Invalid code signature"));
+    }
+
+    #[test]
+    fn nothing_changes_when_already_in_the_requested_state() {
+        let untouched = |_| -> Result<(), String> { panic!("should not register or unregister") };
+        assert_eq!(set_enabled_with(true, || Some(2), untouched), Ok(LoginItemStatus::PendingApproval));
+        assert_eq!(set_enabled_with(false, || Some(0), untouched), Ok(LoginItemStatus::Disabled));
+        assert_eq!(set_enabled_with(true, || None, untouched), Ok(LoginItemStatus::Unsupported));
+    }
+}
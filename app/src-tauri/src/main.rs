@@ -4,38 +4,274 @@
 mod permissions;
 mod bridge;
 mod config;
+mod diagnostics;
+mod error;
+mod headless;
+mod locks;
+mod login_item;
+mod metrics;
+mod polling;
+mod preflight;
+mod setup;
 
 use tauri::{
-    menu::{Menu, MenuItem},
-    Manager,
+    menu::{Menu, MenuItem, PredefinedMenuItem},
+    Emitter, Listener, Manager,
 };
+use locks::LockExt;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
+/// Set once the app starts quitting, so late status events leave the tray alone
+static QUITTING: AtomicBool = AtomicBool::new(false);
+
 struct AppState {
     running: Mutex<bool>,
     tunnel_url: Mutex<Option<String>>,
     api_secret: Mutex<Option<String>>,
+    /// Worker and bridge are suspended while the tunnel stays up
+    paused: Mutex<bool>,
+    lifecycle: Lifecycle,
+}
+
+/// Serializes start/stop operations end-to-end so they can't interleave
+#[derive(Default)]
+struct Lifecycle(tokio::sync::Mutex<()>);
+
+impl Lifecycle {
+    /// Claim the lifecycle, failing fast if another operation is in flight
+    fn try_begin(&self) -> Result<tokio::sync::MutexGuard<'_, ()>, error::AppError> {
+        self.0.try_lock().map_err(|_| error::AppError::Busy)
+    }
+
+    /// Wait for any in-flight operation to finish, then claim the lifecycle
+    async fn begin(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
+
+/// Result of starting the tunnel, as returned to the frontend
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TunnelInfo {
+    success: bool,
+    url: Option<String>,
+    api_secret: Option<String>,
+    error: Option<String>,
+    /// The user cancelled the start, so `error` isn't a failure to report
+    cancelled: bool,
+    /// Set on success: whether a tunnel was started or one was already up
+    outcome: Option<bridge::StartOutcome>,
+}
+
+/// Result of `start_local_server`, as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerStart {
+    outcome: bridge::StartOutcome,
+    /// The new session's secret when started; the running session's when it was already up
+    api_secret: Option<String>,
+}
+
+/// What `rotate_all_secrets` changed
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RotationReport {
+    api_secret: String,
+    project_files_rewritten: bool,
+    server_restarted: bool,
+    /// Why the running server couldn't be restarted with the new secret; it's stopped and the files still have the new one
+    server_error: Option<String>,
+    worker_secrets_updated: Vec<String>,
+    worker_error: Option<String>,
+}
+
+/// What `reset_all` cleared
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResetReport {
+    processes_stopped: bool,
+    config_backup: Option<String>,
+    files_removed: Vec<String>,
+    project_error: Option<String>,
+}
+
+/// Where `set_mode` left things
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModeReport {
+    mode: setup::Mode,
+    /// A worker deploy stays up in local mode; the UI says so rather than pretending it's gone
+    deployed: bool,
+    deployed_url: Option<String>,
+    status: SystemStatus,
+}
+
+/// Phrase `reset_all` must be called with
+const RESET_CONFIRMATION: &str = "RESET";
+
+/// Snapshot of the whole system, as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemStatus {
+    running: bool,
+    paused: bool,
+    tunnel_url: Option<String>,
+    /// When the current run began; pausing doesn't reset it
+    started_at: Option<String>,
+    uptime_secs: Option<u64>,
+    processes: Vec<bridge::ProcessStatus>,
 }
 
 #[tauri::command]
-async fn check_config() -> Result<serde_json::Value, String> {
+async fn check_config(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let first_run = config::seed_if_first_run().map_err(|e| e.to_string())?;
     let config = config::load_config().map_err(|e| e.to_string())?;
+    let mut warnings = config.validate();
+    
+    // A saved tunnel we aren't running ourselves may have gone away
+    // The probe is a curl that can take seconds, so it stays off the async runtime
+    let running = *state.running.lock_or_recover();
+    if let Some(url) = config.tunnel_url.clone().filter(|_| !running) {
+        let probe_config = config.clone();
+        let reachable = tauri::async_runtime::spawn_blocking(move || bridge::tunnel_health(&url, None, &probe_config))
+            .await
+            .map_err(|e| e.to_string())?;
+        if !reachable {
+            warnings.push(config::ConfigWarning {
+                field: "tunnelUrl".to_string(),
+                message: "The saved tunnel URL is no longer reachable - start the tunnel again".to_string(),
+            });
+        }
+    }
+    
+    if let Some(dir) = config::config_dir_fallback() {
+        warnings.push(config::ConfigWarning {
+            field: "configDir".to_string(),
+            message: format!("Application Support isn't writable on this Mac, so settings are kept in {}", dir.display()),
+        });
+    }
     
     Ok(serde_json::json!({
         "configured": config.anthropic_key.is_some(),
+        "firstRun": first_run,
         "tunnelUrl": config.tunnel_url,
+        "warnings": warnings,
     }))
 }
 
+/// Wait for an API key to land in the config, e.g. from a hand edit, instead of polling `check_config`
+/// Returns whether it's configured, which is false only on timeout
+#[tauri::command]
+async fn wait_until_configured(timeout_ms: u64) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        config::wait_for(std::time::Duration::from_millis(timeout_ms), |config| config.anthropic_key.is_some())
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_setup_state(state: tauri::State<'_, AppState>) -> Result<setup::SetupState, String> {
+    let config = config::load_config().unwrap_or_default();
+    let inputs = setup::SetupInputs {
+        has_api_key: config.anthropic_key.is_some(),
+        permissions_granted: permissions::check_required(&config).complete,
+        server_running: bridge::local_server_running(),
+        tunnel_running: state.tunnel_url.lock_or_recover().is_some(),
+        wants_deploy: config.mode.as_deref() == Some("remote"),
+        deployed: config.deployed.unwrap_or(false),
+    };
+    let step = setup::derive_step(inputs);
+    
+    Ok(setup::SetupState {
+        step,
+        next_action: step.next_action().to_string(),
+        setup_complete: config.setup_complete.unwrap_or(false),
+    })
+}
+
+#[tauri::command]
+async fn mark_setup_complete() -> Result<(), String> {
+    let mut config = config::load_config().unwrap_or_default();
+    config.setup_complete = Some(true);
+    config::save_config(&config).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn check_permissions() -> Result<serde_json::Value, String> {
     let results = permissions::check_all();
     Ok(serde_json::json!(results))
 }
 
+/// Every permission and Automation app, split into what onboarding requires and what's optional
 #[tauri::command]
-async fn request_permission(permission: String) -> Result<(), String> {
-    permissions::request(&permission).map_err(|e| e.to_string())
+async fn check_required_permissions() -> Result<permissions::PermissionSummary, String> {
+    tauri::async_runtime::spawn_blocking(|| permissions::check_required(&config::load_config().unwrap_or_default()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Check every permission at once, emitting `system://permission-checked` as each finishes
+/// Gives up after `deadline_ms` or on `cancel_permission_checks`, with unfinished checks reported as unknown
+#[tauri::command]
+async fn check_permissions_cancellable(
+    deadline_ms: u64,
+    app: tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, permissions::CheckStatus>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        permissions::check_all_cancellable(std::time::Duration::from_millis(deadline_ms), |name, granted| {
+            let _ = app.emit("system://permission-checked", serde_json::json!({
+                "name": name,
+                "granted": granted,
+            }));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cancel_permission_checks() -> Result<(), String> {
+    permissions::cancel_checks();
+    Ok(())
+}
+
+/// Re-check one permission, e.g. after the user comes back from System Settings
+#[tauri::command]
+async fn check_permission(name: String) -> Result<permissions::PermissionState, String> {
+    tauri::async_runtime::spawn_blocking(move || permissions::check(&name).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Do one real thing with a permission so the wizard can show a definitive green check
+#[tauri::command]
+async fn self_test_permission(permission: String) -> Result<permissions::SelfTest, String> {
+    tauri::async_runtime::spawn_blocking(move || permissions::self_test(&permission).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn preflight() -> Result<Vec<preflight::CheckResult>, String> {
+    Ok(preflight::run())
+}
+
+#[tauri::command]
+async fn request_permission(permission: String) -> Result<permissions::PermissionRequest, String> {
+    tauri::async_runtime::spawn_blocking(move || permissions::request(&permission).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Open the next permission the user still needs to grant, in onboarding order
+#[tauri::command]
+async fn request_next_missing() -> Result<permissions::OnboardingProgress, String> {
+    tauri::async_runtime::spawn_blocking(|| permissions::request_next_missing().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -53,49 +289,125 @@ async fn prewarm_app(app_name: String) -> Result<bool, String> {
     Ok(permissions::prewarm_app(&app_name))
 }
 
+/// Prewarm several apps, `concurrency` at a time (3 by default), emitting each result as it lands
+#[tauri::command]
+async fn prewarm_apps(
+    app_names: Vec<String>,
+    concurrency: Option<usize>,
+    app: tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, permissions::PrewarmOutcome>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let total = app_names.len();
+        let mut done = 0;
+        permissions::prewarm_apps(&app_names, concurrency.unwrap_or(permissions::PREWARM_CONCURRENCY), |name, outcome| {
+            done += 1;
+            let _ = app.emit("system://prewarm-progress", serde_json::json!({
+                "app": name,
+                "outcome": outcome,
+                "done": done,
+                "total": total,
+            }));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn refresh_automation_status(app_name: String) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || permissions::refresh_automation_status(&app_name))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn detect_node_versions() -> Result<Vec<bridge::NodeCandidate>, String> {
+    tauri::async_runtime::spawn_blocking(bridge::detect_node_versions)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn discover_projects() -> Result<Vec<String>, String> {
+    let config = config::load_config().ok();
+    Ok(bridge::discover_project_roots(config.as_ref())
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect())
+}
+
 #[tauri::command]
 async fn save_api_key(api_key: String) -> Result<(), String> {
     let mut config = config::load_config().unwrap_or_default();
     config.anthropic_key = Some(api_key);
-    
-    // Find and save project root
-    match bridge::find_project_root(Some(&config)) {
-        Ok(root) => {
-            config.project_root = Some(root.to_string_lossy().to_string());
-        }
-        Err(e) => {
-            return Err(format!("Could not find SYSTEM project: {}", e));
-        }
-    }
-    
+    keep_or_find_project_root(&mut config)?;
     config::save_config(&config).map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Leave a saved project root alone, e.g. one the user picked; one that no longer holds the project
+/// is reported rather than replaced, and the project is only searched for when no root is saved
+fn keep_or_find_project_root(config: &mut config::Config) -> Result<(), String> {
+    if config.project_root.as_deref().is_some_and(|root| config::normalize_project_root(root).is_ok()) {
+        return Ok(());
+    }
+    let root = bridge::find_project_root(Some(config)).map_err(|e| e.to_string())?;
+    config.project_root = Some(config::normalize_project_root(&root.to_string_lossy()).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Point the app at a project folder the user picked, storing it in canonical form
 #[tauri::command]
-async fn start_local_server(state: tauri::State<'_, AppState>) -> Result<String, String> {
+async fn set_project_root(path: String) -> Result<String, String> {
+    let root = config::normalize_project_root(&path).map_err(|e| e.to_string())?;
+    let mut config = config::load_config().unwrap_or_default();
+    config.project_root = Some(root.clone());
+    config::save_config(&config).map_err(|e| e.to_string())?;
+    Ok(root)
+}
+
+/// Start the worker and bridge under a fresh API secret, keeping state in step
+/// Callers must hold the lifecycle
+async fn launch_local_server(app: &tauri::AppHandle, state: &AppState) -> Result<String, String> {
     // Generate a new secure token for this session
     let token = bridge::generate_token();
     
     // Store the token in app state
-    *state.api_secret.lock().unwrap() = Some(token.clone());
+    *state.api_secret.lock_or_recover() = Some(token.clone());
     
     // Start the server with the generated token
-    bridge::start_local_server(&token).await.map_err(|e| e.to_string())?;
+    bridge::start_local_server(&token, &|stage, status| {
+        if status == "failed" {
+            metrics::record(format!("start_local_server.{}.failed", stage));
+        }
+        let _ = app.emit("system://startup-progress", serde_json::json!({
+            "stage": stage,
+            "status": status,
+        }));
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    metrics::record("start_local_server.ok");
+    *state.paused.lock_or_recover() = false;
+    
+    // A tunnel left running from before the restart makes the system whole again
+    *state.running.lock_or_recover() = bridge::is_running(bridge::ProcessKind::Tunnel);
     
-    // Return the token so frontend can display it
     Ok(token)
 }
 
-#[tauri::command]
-async fn start_tunnel(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
-    match bridge::start_tunnel_and_get_url().await {
+/// Start cloudflared in front of the local server and record its URL
+/// Callers must hold the lifecycle
+async fn launch_tunnel(app: &tauri::AppHandle, state: &AppState) -> TunnelInfo {
+    let handle = app.clone();
+    match bridge::start_tunnel_and_get_url(move |old, new| on_tunnel_url_changed(&handle, old, new)).await {
         Ok(url) => {
-            *state.tunnel_url.lock().unwrap() = Some(url.clone());
-            *state.running.lock().unwrap() = true;
+            metrics::record("start_tunnel.ok");
+            *state.tunnel_url.lock_or_recover() = Some(url.clone());
+            *state.running.lock_or_recover() = true;
             
             // Get the stored API secret
-            let api_secret = state.api_secret.lock().unwrap().clone();
+            let api_secret = state.api_secret.lock_or_recover().clone();
             
             // Save tunnel URL to config
             if let Ok(mut config) = config::load_config() {
@@ -103,38 +415,995 @@ async fn start_tunnel(state: tauri::State<'_, AppState>) -> Result<serde_json::V
                 let _ = config::save_config(&config);
             }
             
-            Ok(serde_json::json!({
-                "success": true,
-                "url": url,
-                "apiSecret": api_secret,
-            }))
+            // A tunnel only starts in front of a running server, so this is the system coming up
+            announce_ready(app, &url, api_secret.is_some());
+            
+            TunnelInfo {
+                success: true,
+                url: Some(url),
+                api_secret,
+                outcome: Some(bridge::StartOutcome::Started),
+                ..Default::default()
+            }
+        }
+        Err(e) => {
+            let cancelled = matches!(e.downcast_ref::<error::AppError>(), Some(error::AppError::Cancelled));
+            metrics::record(match e.downcast_ref::<error::AppError>() {
+                Some(error::AppError::Cancelled) => "start_tunnel.cancelled",
+                Some(error::AppError::TunnelTimeout { .. }) => "start_tunnel.timeout",
+                Some(error::AppError::TunnelRateLimited) => "start_tunnel.rate_limited",
+                _ => "start_tunnel.failed",
+            });
+            TunnelInfo {
+                success: false,
+                cancelled,
+                error: Some(e.to_string()),
+                ..Default::default()
+            }
         }
-        Err(e) => Ok(serde_json::json!({
-            "success": false,
-            "error": e.to_string(),
-        }))
     }
 }
 
+/// Start the local server, or report that it's already up without restarting it
+#[tauri::command]
+async fn start_local_server(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<ServerStart, String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    if bridge::local_server_running() {
+        return Ok(ServerStart {
+            outcome: bridge::StartOutcome::AlreadyRunning,
+            api_secret: state.api_secret.lock_or_recover().clone(),
+        });
+    }
+    
+    // Return the token so frontend can display it
+    let result = launch_local_server(&app, &state).await;
+    status_changed(&app);
+    result.map(|token| ServerStart { outcome: bridge::StartOutcome::Started, api_secret: Some(token) })
+}
+
+/// Check a key (or the saved one) against the Anthropic API, through the configured gateway if there is one
 #[tauri::command]
-async fn stop_system(state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn test_api_key(key: Option<String>) -> Result<(), String> {
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    let key = key.or(config.anthropic_key.clone()).ok_or("No API key to test - enter your Anthropic API key")?;
+    let base_url = config
+        .anthropic_base_url()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| config::DEFAULT_ANTHROPIC_BASE_URL.to_string());
+    tauri::async_runtime::spawn_blocking(move || bridge::test_api_key(&key, &base_url, &config))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn check_ports() -> Result<Vec<bridge::PortCheck>, String> {
+    tauri::async_runtime::spawn_blocking(bridge::check_ports)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn plan_start() -> Result<bridge::LaunchPlan, String> {
+    bridge::plan_start().map_err(|e| e.to_string())
+}
+
+/// Exact argv, working directory, and environment for each process, to rerun one by hand and see its real error
+#[tauri::command]
+async fn get_launch_commands() -> Result<Vec<bridge::LaunchCommand>, String> {
+    tauri::async_runtime::spawn_blocking(|| bridge::launch_commands().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn start_tunnel(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<TunnelInfo, String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    let running_url = state.tunnel_url.lock_or_recover().clone().filter(|_| bridge::is_running(bridge::ProcessKind::Tunnel));
+    if let Some(url) = running_url {
+        return Ok(TunnelInfo {
+            success: true,
+            url: Some(url),
+            api_secret: state.api_secret.lock_or_recover().clone(),
+            outcome: Some(bridge::StartOutcome::AlreadyRunning),
+            ..Default::default()
+        });
+    }
+    let info = launch_tunnel(&app, &state).await;
+    status_changed(&app);
+    Ok(info)
+}
+
+/// Bring the whole system up in one call: the local server, then (when `remote`) the tunnel
+/// Anything that fails part-way is torn down again so there's never a half-started system
+#[tauri::command]
+async fn start_system(remote: bool, app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<SystemStatus, String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    bridge::reset_process_stats();
+    
+    let result = match launch_local_server(&app, &state).await {
+        Ok(_) if remote => {
+            let _ = app.emit("system://startup-progress", serde_json::json!({
+                "stage": "start_tunnel",
+                "status": "running",
+            }));
+            let tunnel = launch_tunnel(&app, &state).await;
+            let _ = app.emit("system://startup-progress", serde_json::json!({
+                "stage": "start_tunnel",
+                "status": if tunnel.success { "done" } else { "failed" },
+            }));
+            match tunnel.error {
+                Some(e) if !tunnel.success => Err(e),
+                _ => Ok(()),
+            }
+        }
+        Ok(_) => {
+            // Local mode has nothing more to start
+            *state.running.lock_or_recover() = true;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    };
+    
+    if let Err(e) = result {
+        let _ = bridge::stop_all().await;
+        *state.running.lock_or_recover() = false;
+        *state.tunnel_url.lock_or_recover() = None;
+        *state.api_secret.lock_or_recover() = None;
+        status_changed(&app);
+        return Err(e);
+    }
+    status_changed(&app);
+    Ok(system_status(&state))
+}
+
+/// What a mode switch starts from, read from the config
+fn mode_inputs(config: &config::Config) -> setup::ModeInputs {
+    setup::ModeInputs {
+        has_api_key: config.anthropic_key.is_some(),
+        deployed: config.deployed == Some(true),
+        has_deployed_url: config.deployed_url.is_some(),
+    }
+}
+
+/// Switch between local and remote in one step: stop everything, start what the mode needs, then save it
+/// Remote deploys the worker if there's no finished deploy, and is never saved without one
+#[tauri::command]
+async fn set_mode(mode: String, app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<ModeReport, String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    let transition = setup::plan_mode_change(mode_inputs(&config), &mode)?;
+    
+    // Start the new mode from nothing so no process from the old one lingers
+    let _ = bridge::stop_all().await;
+    *state.running.lock_or_recover() = false;
+    *state.paused.lock_or_recover() = false;
+    *state.tunnel_url.lock_or_recover() = None;
+    
+    if let Err(e) = enter_mode(&app, &state, transition).await {
+        let _ = bridge::stop_all().await;
+        *state.running.lock_or_recover() = false;
+        *state.tunnel_url.lock_or_recover() = None;
+        *state.api_secret.lock_or_recover() = None;
+        status_changed(&app);
+        return Err(e);
+    }
+    status_changed(&app);
+    
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    Ok(ModeReport {
+        mode: transition.target,
+        deployed: config.deployed == Some(true),
+        deployed_url: config.deployed_url,
+        status: system_status(&state),
+    })
+}
+
+/// Start what `transition` calls for and record the mode once it's all up
+/// Callers must hold the lifecycle
+async fn enter_mode(app: &tauri::AppHandle, state: &AppState, transition: setup::ModeTransition) -> Result<(), String> {
+    let token = launch_local_server(app, state).await?;
+    if transition.start_tunnel {
+        let tunnel = launch_tunnel(app, state).await;
+        if !tunnel.success {
+            return Err(tunnel.error.unwrap_or_else(|| "The tunnel didn't start".to_string()));
+        }
+    } else {
+        *state.running.lock_or_recover() = true;
+    }
+    
+    if transition.target == setup::Mode::Remote {
+        let handle = app.clone();
+        let secret = token.clone();
+        let deploy = transition.deploy;
+        tauri::async_runtime::spawn_blocking(move || {
+            if deploy {
+                let report = bridge::deploy_worker(&secret, &|stage, status| {
+                    let _ = handle.emit("system://deploy-progress", serde_json::json!({
+                        "stage": stage,
+                        "status": status,
+                    }));
+                })
+                .map_err(|e| e.to_string())?;
+                report.error.map_or(Ok(()), Err)
+            } else {
+                // The existing worker has to accept this session's new secret
+                bridge::push_auth_secrets(&secret).map(|_| ()).map_err(|e| e.to_string())
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    
+    let mut config = config::load_config().map_err(|e| e.to_string())?;
+    if transition.target == setup::Mode::Remote {
+        setup::check_remote_ready(mode_inputs(&config))?;
+    }
+    config.mode = Some(transition.target.as_str().to_string());
+    config::save_config(&config).map_err(|e| e.to_string())
+}
+
+/// Give up on an in-flight `start_tunnel` and stop the cloudflared it spawned
+#[tauri::command]
+async fn cancel_tunnel() -> Result<(), String> {
+    bridge::cancel_tunnel();
+    Ok(())
+}
+
+#[tauri::command]
+async fn deploy_worker(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<bridge::DeployReport, String> {
+    let api_secret = state.api_secret.lock_or_recover().clone()
+        .or_else(|| config::load_config().ok().and_then(|c| c.auth_token))
+        .ok_or("Start the local server first so there is an API secret to deploy")?;
+    
+    tauri::async_runtime::spawn_blocking(move || {
+        bridge::deploy_worker(&api_secret, &|stage, status| {
+            if status != "running" {
+                metrics::record(format!("deploy_worker.{}.{}", stage, status));
+            }
+            let _ = app.emit("system://deploy-progress", serde_json::json!({
+                "stage": stage,
+                "status": status,
+            }));
+        })
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_metrics() -> Result<metrics::Metrics, String> {
+    Ok(metrics::load())
+}
+
+#[tauri::command]
+async fn reset_metrics() -> Result<(), String> {
+    metrics::reset().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn check_wrangler_auth() -> Result<bridge::WranglerAuth, String> {
+    tauri::async_runtime::spawn_blocking(|| bridge::check_wrangler_auth().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_cloudflare_accounts() -> Result<Vec<bridge::CloudflareAccount>, String> {
+    let auth = tauri::async_runtime::spawn_blocking(bridge::check_wrangler_auth)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    if !auth.authenticated {
+        return Err(auth.hint.unwrap_or_else(|| "Not logged in to Cloudflare".to_string()));
+    }
+    Ok(auth.accounts)
+}
+
+#[tauri::command]
+async fn set_cloudflare_account(account_id: String) -> Result<(), String> {
+    let account_id = account_id.trim().to_lowercase();
+    if !config::is_valid_account_id(&account_id) {
+        return Err("Cloudflare account ids are 32 hex characters".to_string());
+    }
+    
+    let mut config = config::load_config().unwrap_or_default();
+    config.cloudflare_account_id = Some(account_id);
+    config::save_config(&config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cleanup_failed_deploy() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(|| bridge::cleanup_failed_deploy().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Secrets set on the deployed worker, so ones a failed deploy left behind can be found
+#[tauri::command]
+async fn list_worker_secrets() -> Result<Vec<bridge::WorkerSecret>, String> {
+    tauri::async_runtime::spawn_blocking(|| bridge::list_worker_secrets().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Delete a secret from the deployed worker
+/// `confirm` must repeat the secret's name so a stray call can't break a working deploy
+#[tauri::command]
+async fn delete_worker_secret(name: String, confirm: String) -> Result<(), String> {
+    if confirm != name {
+        return Err(format!("Delete not confirmed - pass \"{}\" again to delete it", name));
+    }
+    tauri::async_runtime::spawn_blocking(move || bridge::delete_worker_secret(&name).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn build_project() -> Result<bridge::BuildOutput, String> {
+    tauri::async_runtime::spawn_blocking(|| bridge::build_project().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Tell the UI (and the log) everything about a system that just came up, in one event
+fn announce_ready(app: &tauri::AppHandle, tunnel_url: &str, has_api_secret: bool) {
+    let config = config::load_config().unwrap_or_default();
+    let summary = serde_json::json!({
+        "tunnelUrl": tunnel_url,
+        "hasApiSecret": has_api_secret,
+        "workerPort": config.worker_port(),
+        "bridgePort": config.bridge_port(),
+        "projectRoot": config.project_root,
+        "deployed": config.deployed.unwrap_or(false),
+        "processes": bridge::process_statuses(),
+    });
+    bridge::log("info", "system", &format!("System ready: {}", summary));
+    let _ = app.emit("system://ready", summary);
+}
+
+/// Keep state, config, and the UI in step when cloudflared comes back under a new URL
+fn on_tunnel_url_changed(app: &tauri::AppHandle, old_url: String, new_url: String) {
+    *app.state::<AppState>().tunnel_url.lock_or_recover() = Some(new_url.clone());
+    
+    if let Ok(mut config) = config::load_config() {
+        config.tunnel_url = Some(new_url.clone());
+        let _ = config::save_config(&config);
+    }
+    
+    let _ = app.emit("system://tunnel-url-changed", serde_json::json!({
+        "oldUrl": old_url,
+        "newUrl": new_url,
+    }));
+    status_changed(app);
+}
+
+#[tauri::command]
+async fn rotate_all_secrets(
+    push_to_worker: bool,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<RotationReport, String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    let token = bridge::generate_token();
+    let mut report = RotationReport { api_secret: token.clone(), ..Default::default() };
+    
+    // A running server is restarted with the new secret (which rewrites the files);
+    // otherwise just rewrite the files so the next start picks it up
+    if bridge::local_server_running() {
+        // The restart replaces the old processes and files even when it fails, so the new secret is current from here on
+        *state.api_secret.lock_or_recover() = Some(token.clone());
+        let restarted = bridge::start_local_server(&token, &|stage, status| {
+            let _ = app.emit("system://startup-progress", serde_json::json!({
+                "stage": stage,
+                "status": status,
+            }));
+        })
+        .await;
+        match restarted {
+            Ok(()) => {
+                report.server_restarted = true;
+                report.project_files_rewritten = true;
+            }
+            Err(e) => {
+                report.server_error = Some(e.to_string());
+                // It may have failed before writing the files, so make sure they carry the new secret
+                report.project_files_rewritten = bridge::rewrite_project_secrets(&token).is_ok();
+            }
+        }
+    } else {
+        bridge::rewrite_project_secrets(&token).map_err(|e| e.to_string())?;
+        report.project_files_rewritten = true;
+        *state.api_secret.lock_or_recover() = Some(token.clone());
+    }
+    
+    // Only a deployed worker has secrets to update
+    let deployed = config::load_config().map(|c| c.deployed == Some(true)).unwrap_or(false);
+    if push_to_worker && deployed {
+        let secret = token.clone();
+        match tauri::async_runtime::spawn_blocking(move || bridge::push_auth_secrets(&secret).map_err(|e| e.to_string())).await {
+            Ok(Ok(updated)) => report.worker_secrets_updated = updated,
+            Ok(Err(e)) => report.worker_error = Some(e),
+            Err(e) => report.worker_error = Some(e.to_string()),
+        }
+    }
+    
+    Ok(report)
+}
+
+#[tauri::command]
+async fn stop_system(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let _lifecycle = state.lifecycle.begin().await;
     bridge::stop_all().await.map_err(|e| e.to_string())?;
-    *state.running.lock().unwrap() = false;
-    *state.tunnel_url.lock().unwrap() = None;
+    *state.running.lock_or_recover() = false;
+    *state.paused.lock_or_recover() = false;
+    *state.tunnel_url.lock_or_recover() = None;
+    status_changed(&app);
+    Ok(())
+}
+
+/// Suspend the worker and bridge so nothing runs on this Mac, keeping the tunnel and its URL
+#[tauri::command]
+async fn pause_system(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    bridge::pause().map_err(|e| e.to_string())?;
+    *state.paused.lock_or_recover() = true;
+    status_changed(&app);
+    Ok(())
+}
+
+/// Let a paused worker and bridge carry on where they left off
+#[tauri::command]
+async fn resume_system(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let _lifecycle = state.lifecycle.try_begin().map_err(|e| e.to_string())?;
+    bridge::resume();
+    *state.paused.lock_or_recover() = false;
+    status_changed(&app);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
-    let running = *state.running.lock().unwrap();
-    let url = state.tunnel_url.lock().unwrap().clone();
+async fn stop_process(which: bridge::ProcessKind, app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let _lifecycle = state.lifecycle.begin().await;
+    bridge::stop_async(which).await;
     
-    Ok(serde_json::json!({
-        "running": running,
-        "tunnelUrl": url,
+    // Any missing component means the system is no longer fully running
+    *state.running.lock_or_recover() = false;
+    if which == bridge::ProcessKind::Tunnel {
+        *state.tunnel_url.lock_or_recover() = None;
+    }
+    status_changed(&app);
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_orphans() -> Result<Vec<bridge::OrphanProcess>, String> {
+    Ok(bridge::find_orphans())
+}
+
+#[tauri::command]
+async fn kill_orphans(state: tauri::State<'_, AppState>) -> Result<Vec<u32>, String> {
+    let _lifecycle = state.lifecycle.begin().await;
+    Ok(bridge::kill_orphans())
+}
+
+#[tauri::command]
+async fn get_status(state: tauri::State<'_, AppState>) -> Result<SystemStatus, String> {
+    Ok(system_status(&state))
+}
+
+fn system_status(state: &AppState) -> SystemStatus {
+    let running = *state.running.lock_or_recover();
+    let url = state.tunnel_url.lock_or_recover().clone();
+    let processes = bridge::process_statuses();
+    
+    // Uptime only means something while the system is up
+    let started_at = bridge::started_at().filter(|_| processes.iter().any(|p| p.running));
+    
+    SystemStatus {
+        running,
+        paused: *state.paused.lock_or_recover(),
+        tunnel_url: url,
+        started_at: started_at.map(bridge::format_started_at),
+        uptime_secs: started_at.and_then(|t| t.elapsed().ok()).map(|d| d.as_secs()),
+        processes,
+    }
+}
+
+/// Current tail of one log (wrangler, bridge, or tunnel), optionally streaming new lines as `system://log`
+#[tauri::command]
+async fn tail_log(source: String, follow: bool, app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let kind = bridge::ProcessKind::from_log_name(&source)
+        .ok_or_else(|| format!("Unknown log source: {} (expected wrangler, bridge, or tunnel)", source))?;
+    Ok(bridge::tail_log(kind, follow, move |line| {
+        let _ = app.emit("system://log", serde_json::json!({
+            "source": source,
+            "line": line,
+        }));
     }))
 }
 
+#[tauri::command]
+async fn untail_log(source: String) -> Result<(), String> {
+    let kind = bridge::ProcessKind::from_log_name(&source)
+        .ok_or_else(|| format!("Unknown log source: {}", source))?;
+    bridge::unfollow_log(kind);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_process_diagnostics() -> Result<Vec<bridge::ProcessDiagnostics>, String> {
+    Ok(bridge::process_diagnostics())
+}
+
+/// Versions of cloudflared and wrangler, with upgrade hints for ones known to misbehave
+#[tauri::command]
+async fn check_tool_versions() -> Result<Vec<bridge::ToolVersion>, String> {
+    tauri::async_runtime::spawn_blocking(bridge::check_tool_versions)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Match recent process logs against known failures and suggest fixes
+#[tauri::command]
+async fn scan_logs_for_errors() -> Result<Vec<diagnostics::LogMatch>, String> {
+    tauri::async_runtime::spawn_blocking(diagnostics::scan_logs)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The resolved config with each field's source, for "it's not using my setting" reports
+#[tauri::command]
+async fn get_effective_config() -> Result<diagnostics::EffectiveConfig, String> {
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    Ok(diagnostics::effective_config(&config))
+}
+
+/// Gather the redacted bundle so the user can review it before exporting
+#[tauri::command]
+async fn collect_diagnostics(state: tauri::State<'_, AppState>) -> Result<diagnostics::DiagnosticBundle, String> {
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    tauri::async_runtime::spawn_blocking(move || diagnostics::collect(api_secret.as_deref()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Write the redacted bundle to `path` and return where it went
+#[tauri::command]
+async fn export_diagnostics(path: String, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let bundle = diagnostics::collect(api_secret.as_deref());
+        diagnostics::write(&bundle, std::path::Path::new(&path)).map_err(|e| e.to_string())?;
+        Ok(path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Start over: stop everything, back up and remove the config, delete the project's secret files and forget the session
+/// `confirm` must be "RESET" so a stray call can't wipe a working setup
+#[tauri::command]
+async fn reset_all(confirm: String, state: tauri::State<'_, AppState>) -> Result<ResetReport, String> {
+    if confirm != RESET_CONFIRMATION {
+        return Err(format!("Reset not confirmed - pass \"{}\" to clear everything", RESET_CONFIRMATION));
+    }
+    let _lifecycle = state.lifecycle.begin().await;
+    let mut report = ResetReport {
+        processes_stopped: bridge::stop_all().await.is_ok(),
+        ..Default::default()
+    };
+    
+    // The project is found through the config, so clear its files first
+    match bridge::clear_project_secrets() {
+        Ok(removed) => report.files_removed = removed.iter().map(|p| p.display().to_string()).collect(),
+        Err(e) => report.project_error = Some(e.to_string()),
+    }
+    
+    report.config_backup = config::reset()
+        .map_err(|e| e.to_string())?
+        .map(|p| p.display().to_string());
+    
+    *state.running.lock_or_recover() = false;
+    *state.paused.lock_or_recover() = false;
+    *state.tunnel_url.lock_or_recover() = None;
+    *state.api_secret.lock_or_recover() = None;
+    
+    Ok(report)
+}
+
+/// Rewrite `.dev.vars` from the current config so wrangler dev picks up a new key or base URL without a restart
+#[tauri::command]
+async fn sync_dev_vars(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let api_secret = state.api_secret.lock_or_recover().clone()
+        .ok_or("SYSTEM isn't running, so there's no session to sync - start it instead")?;
+    tauri::async_runtime::spawn_blocking(move || bridge::sync_dev_vars(&api_secret).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// What's in the project's `.dev.vars` and `bridge.config.json`, with secrets redacted
+#[tauri::command]
+async fn inspect_project_secrets(state: tauri::State<'_, AppState>) -> Result<Vec<bridge::SecretFileReport>, String> {
+    let session_secret = state.api_secret.lock_or_recover().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        bridge::inspect_project_secrets(session_secret.as_deref()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete the project's secret-bearing files, returning the paths removed
+#[tauri::command]
+async fn clear_project_secrets() -> Result<Vec<String>, String> {
+    let removed = bridge::clear_project_secrets().map_err(|e| e.to_string())?;
+    Ok(removed.iter().map(|p| p.display().to_string()).collect())
+}
+
+/// Open the chat UI in the default browser: the running tunnel, or the deployed worker if there's no tunnel
+/// The UI asks for the API secret itself, so it's never put in the URL (or the browser history)
+#[tauri::command]
+async fn open_tunnel_url(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    use tauri_plugin_shell::ShellExt;
+    let tunnel_url = state.tunnel_url.lock_or_recover().clone();
+    let url = tunnel_url
+        .or_else(|| config::load_config().ok().and_then(|c| c.deployed_url))
+        .ok_or("No tunnel or deployed URL yet - start the tunnel first")?;
+    
+    // The shell plugin is already set up (and backs the frontend's own `open`), so reuse it over adding the opener plugin
+    #[allow(deprecated)]
+    app.shell().open(&url, None).map_err(|e| format!("Couldn't open the browser: {}", e))?;
+    Ok(url)
+}
+
+/// Tunnel URL and API secret laid out for pasting into the worker UI or another device
+fn connection_info(url: &str, api_secret: Option<&str>) -> String {
+    match api_secret {
+        Some(secret) => format!("URL: {}\nAPI secret: {}", url, secret),
+        None => format!("URL: {}", url),
+    }
+}
+
+/// Put `text` on the system clipboard
+fn copy_to_clipboard(app: &tauri::AppHandle, text: String) -> Result<(), String> {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+    app.clipboard().write_text(text).map_err(|e| format!("Couldn't copy to the clipboard: {}", e))
+}
+
+/// The running tunnel's URL, or an error saying there's nothing to copy
+fn running_tunnel_url(state: &AppState) -> Result<String, String> {
+    state.tunnel_url.lock_or_recover().clone()
+        .ok_or_else(|| "Nothing is running - start SYSTEM before copying its connection details".to_string())
+}
+
+/// Copy the tunnel URL and API secret to the clipboard
+/// Returns what was copied with the secret masked; the clipboard gets it in full
+#[tauri::command]
+async fn copy_connection_info(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let url = running_tunnel_url(&state)?;
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    copy_to_clipboard(&app, connection_info(&url, api_secret.as_deref()))?;
+    Ok(connection_info(&url, api_secret.as_deref().map(config::redact).as_deref()))
+}
+
+/// Copy just the tunnel URL to the clipboard
+#[tauri::command]
+async fn copy_tunnel_url(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let url = running_tunnel_url(&state)?;
+    copy_to_clipboard(&app, url.clone())?;
+    Ok(url)
+}
+
+/// Check that the deployed worker points at this session's tunnel and accepts its secret
+#[tauri::command]
+async fn verify_deployment(state: tauri::State<'_, AppState>) -> Result<bridge::DeploymentCheck, String> {
+    let config = config::load_config().map_err(|e| e.to_string())?;
+    let deployed_url = config.deployed_url.clone().ok_or("No deployed worker - deploy first")?;
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    let tunnel_url = state.tunnel_url.lock_or_recover().clone().or(config.tunnel_url.clone());
+    tauri::async_runtime::spawn_blocking(move || {
+        bridge::verify_deployment(&deployed_url, api_secret.as_deref(), tunnel_url.as_deref(), &config)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Time requests straight to the local server and through the tunnel, to see what the tunnel adds
+#[tauri::command]
+async fn measure_latency(state: tauri::State<'_, AppState>) -> Result<bridge::LatencyReport, String> {
+    let tunnel_url = state.tunnel_url.lock_or_recover().clone();
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        bridge::measure_latency(tunnel_url.as_deref(), api_secret.as_deref()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_tunnel_health(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    let url = state.tunnel_url.lock_or_recover().clone();
+    let api_secret = state.api_secret.lock_or_recover().clone();
+    match url {
+        Some(url) => Ok(bridge::tunnel_health(&url, api_secret.as_deref(), &config::load_config().unwrap_or_default())),
+        None => Ok(false),
+    }
+}
+
+/// How often a running system's credentials are re-checked
+const AUTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// While the system runs, watch for the worker or Anthropic rejecting our credentials
+/// A persistent rejection emits `system://auth-error` naming which credential to fix
+fn watch_auth(app: tauri::AppHandle) {
+    let mut watch = bridge::AuthWatch::default();
+    loop {
+        std::thread::sleep(AUTH_CHECK_INTERVAL);
+        let state = app.state::<AppState>();
+        if !*state.running.lock_or_recover() || *state.paused.lock_or_recover() {
+            watch = bridge::AuthWatch::default();
+            continue;
+        }
+        let tunnel_url = state.tunnel_url.lock_or_recover().clone();
+        let api_secret = state.api_secret.lock_or_recover().clone();
+        let config = config::load_config().unwrap_or_default();
+        let base_url = config.anthropic_base_url().ok().flatten()
+            .unwrap_or_else(|| config::DEFAULT_ANTHROPIC_BASE_URL.to_string());
+        
+        let result = bridge::check_auth(tunnel_url.as_deref(), api_secret.as_deref(), config.anthropic_key.as_deref(), &base_url, &config);
+        if let Some(failure) = watch.update(result) {
+            bridge::log("warn", "system", failure.message());
+            let _ = app.emit("system://auth-error", serde_json::json!({
+                "kind": failure,
+                "message": failure.message(),
+            }));
+        }
+    }
+}
+
+/// Poll the managed processes and record any that exit unexpectedly
+fn watch_processes(app: tauri::AppHandle) {
+    loop {
+        let exited = bridge::reap_exited();
+        if !exited.is_empty() {
+            let state = app.state::<AppState>();
+            *state.running.lock_or_recover() = false;
+            if exited.contains(&bridge::ProcessKind::Tunnel) {
+                *state.tunnel_url.lock_or_recover() = None;
+            }
+            let _ = app.emit("system://process-exited", bridge::process_diagnostics());
+            status_changed(&app);
+        }
+        polling::wait_for_next_tick();
+    }
+}
+
+/// Poll permissions until the required ones are all granted, then emit `system://permissions-complete` and stop
+fn watch_permissions(app: tauri::AppHandle) {
+    let mut tracker = permissions::CompletionTracker::default();
+    loop {
+        let config = config::load_config().unwrap_or_default();
+        let complete = permissions::is_complete(
+            &permissions::check_all(),
+            &permissions::known_automation_status(),
+            &config.required_permissions(),
+            &config.required_automation_apps(),
+        );
+        if tracker.update(complete) {
+            let _ = app.emit("system://permissions-complete", serde_json::json!({
+                "requiredPermissions": config.required_permissions(),
+                "requiredAutomationApps": config.required_automation_apps(),
+            }));
+            return;
+        }
+        polling::wait_for_next_tick();
+    }
+}
+
+/// Pick up hand edits to config.json and tell the UI
+/// A burst of writes is left to settle first; an edit that doesn't parse is reported and the last good config stays in use
+fn watch_config(app: tauri::AppHandle) {
+    let mut changes = config::watch_changes()
+        .inspect_err(|e| bridge::log("warn", "system", &format!("Can't watch config.json, polling instead: {}", e)))
+        .ok();
+    let mut seen = config::modified();
+    loop {
+        // Wake on the watcher's next event, or on a timer if there's no watcher
+        match changes {
+            Some((_, ref events)) => {
+                if events.recv().is_err() {
+                    changes = None;
+                }
+            }
+            None => std::thread::sleep(polling::WATCH_MAX_INTERVAL),
+        }
+        let mut current = config::modified();
+        if current == seen {
+            continue;
+        }
+        loop {
+            std::thread::sleep(config::WATCH_DEBOUNCE);
+            let settled = config::modified();
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        seen = current;
+        
+        match config::read_config() {
+            Ok(config) => {
+                let _ = app.emit("system://config-changed", serde_json::json!({
+                    "configured": config.anthropic_key.is_some(),
+                    "tunnelUrl": config.tunnel_url,
+                    "warnings": config.validate(),
+                }));
+            }
+            Err(e) => {
+                bridge::log("warn", "system", &format!("Ignoring config.json edit that doesn't parse: {}", e));
+                let _ = app.emit("system://config-invalid", serde_json::json!({
+                    "error": e.to_string(),
+                }));
+            }
+        }
+    }
+}
+
+/// Pick up a tunnel left running by a previous session, or forget it if it's gone
+fn restore_previous_session(app: &tauri::AppHandle) {
+    let mut config = config::load_config().unwrap_or_default();
+    let saved_url = config.tunnel_url.clone();
+    
+    let restored = match saved_url {
+        Some(ref url) if bridge::tunnel_health(url, None, &config) => {
+            let state = app.state::<AppState>();
+            *state.tunnel_url.lock_or_recover() = Some(url.clone());
+            *state.running.lock_or_recover() = true;
+            true
+        }
+        Some(_) => {
+            // Stale URL from a session that's no longer up
+            config.tunnel_url = None;
+            let _ = config::save_config(&config);
+            false
+        }
+        None => false,
+    };
+    
+    let _ = app.emit("system://restored", serde_json::json!({
+        "restored": restored,
+        "tunnelUrl": if restored { saved_url } else { None },
+    }));
+    status_changed(app);
+    
+    // Processes a crashed session left running; the UI offers to kill them (kill_orphans)
+    let leftovers = bridge::leftover_processes();
+    if !leftovers.is_empty() {
+        let _ = app.emit("system://leftover-processes", leftovers);
+    }
+}
+
+/// Start the system on launch when the config asks for it and has what it needs
+async fn auto_start(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    if *state.running.lock_or_recover() {
+        // The previous session is still up
+        return;
+    }
+    let config = config::load_config().unwrap_or_default();
+    match config.auto_start_plan() {
+        config::AutoStartPlan::Off => {}
+        config::AutoStartPlan::MissingKey => {
+            bridge::log("warn", "system", "Auto-start is on but no API key is set - not starting");
+            let _ = app.emit("system://auto-start", serde_json::json!({
+                "started": false,
+                "error": "Auto-start is on but there's no Anthropic API key - finish setup first",
+            }));
+        }
+        config::AutoStartPlan::Start { remote } => {
+            let result = start_system(remote, app.clone(), state).await;
+            if let Err(ref e) = result {
+                bridge::log("error", "system", &format!("Auto-start failed: {}", e));
+            }
+            let _ = app.emit("system://auto-start", serde_json::json!({
+                "started": result.is_ok(),
+                "error": result.err(),
+            }));
+        }
+    }
+}
+
+/// Register or unregister the app as a login item to match `enabled`
+fn sync_launch_at_login(enabled: bool) -> Result<login_item::LoginItemStatus, String> {
+    let status = login_item::set_enabled(enabled);
+    match status {
+        Ok(ref status) => bridge::log("info", "system", &format!("Login item {}: {:?}", if enabled { "registered" } else { "unregistered" }, status)),
+        Err(ref e) => bridge::log("warn", "system", &format!("Couldn't update the login item: {}", e)),
+    }
+    status
+}
+
+/// Save the auto-start settings and update the login item to match
+/// `pendingApproval` means the user still has to allow it in System Settings
+#[tauri::command]
+async fn set_auto_start(auto_start: bool, launch_at_login: bool) -> Result<login_item::LoginItemStatus, String> {
+    let mut config = config::load_config().map_err(|e| e.to_string())?;
+    config.auto_start = Some(auto_start);
+    config.launch_at_login = Some(launch_at_login);
+    config::save_config(&config).map_err(|e| e.to_string())?;
+    sync_launch_at_login(launch_at_login)
+}
+
+/// Whether the app is registered with macOS to run at login
+#[tauri::command]
+async fn get_login_item_enabled() -> Result<login_item::LoginItemStatus, String> {
+    Ok(login_item::status())
+}
+
+/// Save `launchAtLogin` and register or unregister the app to match
+/// `pendingApproval` means the user still has to allow it in System Settings
+#[tauri::command]
+async fn set_login_item_enabled(enabled: bool) -> Result<login_item::LoginItemStatus, String> {
+    let mut config = config::load_config().map_err(|e| e.to_string())?;
+    config.launch_at_login = Some(enabled);
+    config::save_config(&config).map_err(|e| e.to_string())?;
+    sync_launch_at_login(enabled)
+}
+
+/// Tell the UI and the tray that the running state changed
+fn status_changed(app: &tauri::AppHandle) {
+    let status = system_status(&app.state::<AppState>());
+    let _ = app.emit("system://status-changed", status);
+}
+
+/// One-line summary for the tray's status item
+fn tray_status_line(status: &SystemStatus) -> String {
+    if status.paused {
+        return "Paused".to_string();
+    }
+    match status.tunnel_url {
+        Some(ref url) if status.running => {
+            let host = url.trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+            format!("Running - {}", host)
+        }
+        _ if status.running => "Running".to_string(),
+        _ if status.processes.iter().any(|p| p.running) => "Partly running".to_string(),
+        _ => "Stopped".to_string(),
+    }
+}
+
+/// Tray menu for the current state: status, Start/Stop, Open, Quit
+fn build_tray_menu<R: tauri::Runtime, M: Manager<R>>(manager: &M, status: &SystemStatus) -> tauri::Result<Menu<R>> {
+    let up = status.running || status.processes.iter().any(|p| p.running);
+    Menu::with_items(manager, &[
+        &MenuItem::with_id(manager, "status", tray_status_line(status), false, None::<&str>)?,
+        &PredefinedMenuItem::separator(manager)?,
+        &MenuItem::with_id(manager, "start", "Start", !up, None::<&str>)?,
+        &MenuItem::with_id(manager, "stop", "Stop", up, None::<&str>)?,
+        &PredefinedMenuItem::separator(manager)?,
+        &MenuItem::with_id(manager, "open", "Open SYSTEM", true, None::<&str>)?,
+        &MenuItem::with_id(manager, "quit", "Quit", true, None::<&str>)?,
+    ])
+}
+
+/// Rebuild the tray menu from the current state
+fn refresh_tray(app: &tauri::AppHandle) {
+    // The tray may already be going away
+    if QUITTING.load(Ordering::SeqCst) {
+        return;
+    }
+    let Some(tray) = app.tray_by_id("main") else {
+        return;
+    };
+    let status = system_status(&app.state::<AppState>());
+    match build_tray_menu(app, &status) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => bridge::log("warn", "system", &format!("Couldn't update the tray menu: {}", e)),
+    }
+}
+
 #[tauri::command]
 async fn show_window(app: tauri::AppHandle) -> Result<(), String> {
     if let Some(window) = app.get_webview_window("main") {
@@ -144,73 +1413,308 @@ async fn show_window(app: tauri::AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+/// Stop every managed process, then exit; for quits that happen outside a command
+fn exit_after_stopping(app: &tauri::AppHandle) {
+    QUITTING.store(true, Ordering::SeqCst);
+    tauri::async_runtime::block_on(async {
+        let _ = bridge::stop_all().await;
+    });
+    app.exit(0);
+}
+
 #[tauri::command]
-async fn quit_app(app: tauri::AppHandle) -> Result<(), String> {
+async fn quit_app(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
     // Stop everything before quitting
+    QUITTING.store(true, Ordering::SeqCst);
+    let _lifecycle = state.lifecycle.begin().await;
     let _ = bridge::stop_all().await;
     app.exit(0);
     Ok(())
 }
 
 fn main() {
+    // An isolated instance: `system-app --config /path/to/config.json`
+    match config::config_path_arg(std::env::args().skip(1)) {
+        Ok(Some(path)) => {
+            if let Err(e) = config::set_config_path(path) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+    
+    // Run as a daemon without the window: `system-app --headless`
+    if std::env::args().any(|arg| arg == "--headless") {
+        std::process::exit(headless::run());
+    }
+    
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .manage(AppState {
             running: Mutex::new(false),
             tunnel_url: Mutex::new(None),
             api_secret: Mutex::new(None),
+            paused: Mutex::new(false),
+            lifecycle: Lifecycle::default(),
         })
         .setup(|app| {
+            let tray_enabled = config::load_config().unwrap_or_default().tray_enabled();
+            
+            // Window-only: drop the tray icon created by config; closing the window quits instead
+            if !tray_enabled {
+                app.remove_tray_by_id("main");
+            }
+            
             // Create menu for the tray icon
-            let menu = Menu::with_items(app, &[
-                &MenuItem::with_id(app, "open", "Open SYSTEM", true, None::<&str>)?,
-                &MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?,
-            ])?;
+            let menu = build_tray_menu(app, &system_status(&app.state::<AppState>()))?;
             
             // Get the tray icon created by config and set its menu
             if let Some(tray) = app.tray_by_id("main") {
                 tray.set_menu(Some(menu))?;
                 tray.on_menu_event(|app, event| {
                     match event.id.as_ref() {
+                        "start" => {
+                            // Start the way the saved mode says, like auto-start does; local mode gets no tunnel
+                            let remote = config::load_config().unwrap_or_default().is_remote();
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = start_system(remote, app.clone(), app.state::<AppState>()).await {
+                                    bridge::log("error", "system", &format!("Start from the tray failed: {}", e));
+                                }
+                            });
+                        }
+                        "stop" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let _ = stop_system(app.clone(), app.state::<AppState>()).await;
+                            });
+                        }
                         "open" => {
                             if let Some(window) = app.get_webview_window("main") {
                                 let _ = window.show();
                                 let _ = window.set_focus();
                             }
                         }
-                        "quit" => {
-                            tauri::async_runtime::block_on(async {
-                                let _ = bridge::stop_all().await;
-                            });
-                            app.exit(0);
-                        }
+                        "quit" => exit_after_stopping(app),
                         _ => {}
                     }
                 });
             }
             
+            // Keep the tray's status and Start/Stop in step with the system
+            let handle = app.handle().clone();
+            app.listen_any("system://status-changed", move |_| refresh_tray(&handle));
+            
+            // Watch for managed processes dying behind our back
+            let handle = app.handle().clone();
+            std::thread::spawn(move || watch_processes(handle));
+            
+            // Notice a revoked key or an out-of-sync secret while the system runs
+            let handle = app.handle().clone();
+            std::thread::spawn(move || watch_auth(handle));
+            
+            // Tell onboarding the moment every required permission is granted
+            let handle = app.handle().clone();
+            std::thread::spawn(move || watch_permissions(handle));
+            
+            // Apply hand edits to config.json without a restart
+            let handle = app.handle().clone();
+            std::thread::spawn(move || watch_config(handle));
+            
+            // Keep the login item in line with the config
+            let _ = sync_launch_at_login(config::load_config().unwrap_or_default().launch_at_login());
+            
+            // Check whether the last session's tunnel is still alive, then auto-start if asked to
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let restore = handle.clone();
+                let _ = tauri::async_runtime::spawn_blocking(move || restore_previous_session(&restore)).await;
+                auto_start(&handle).await;
+            });
+            
             // Always show window on launch for now
             if let Some(window) = app.get_webview_window("main") {
                 let _ = window.show();
+                
+                // Background pollers tick fast while the window has focus and back off otherwise
+                // Without a tray there's no other way back to the app, so closing the window quits it
+                let handle = app.handle().clone();
+                window.on_window_event(move |event| match event {
+                    tauri::WindowEvent::Focused(focused) => polling::set_poll_active(*focused),
+                    tauri::WindowEvent::CloseRequested { api, .. } if !tray_enabled => {
+                        api.prevent_close();
+                        exit_after_stopping(&handle);
+                    }
+                    _ => {}
+                });
             }
             
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             check_config,
+            reset_all,
+            inspect_project_secrets,
+            sync_dev_vars,
+            clear_project_secrets,
+            wait_until_configured,
+            set_auto_start,
+            get_login_item_enabled,
+            set_login_item_enabled,
+            get_setup_state,
+            mark_setup_complete,
             check_permissions,
+            check_permission,
+            self_test_permission,
+            check_permissions_cancellable,
+            check_required_permissions,
+            cancel_permission_checks,
+            preflight,
             request_permission,
+            request_next_missing,
             get_automation_apps,
             get_automation_apps_with_status,
             prewarm_app,
+            prewarm_apps,
+            refresh_automation_status,
+            discover_projects,
+            detect_node_versions,
             save_api_key,
+            set_project_root,
             start_local_server,
+            plan_start,
+            get_launch_commands,
+            check_ports,
+            test_api_key,
             start_tunnel,
+            start_system,
+            cancel_tunnel,
+            deploy_worker,
+            rotate_all_secrets,
+            check_wrangler_auth,
+            list_cloudflare_accounts,
+            set_cloudflare_account,
+            cleanup_failed_deploy,
+            list_worker_secrets,
+            delete_worker_secret,
+            build_project,
             stop_system,
+            pause_system,
+            resume_system,
+            stop_process,
+            list_orphans,
+            kill_orphans,
             get_status,
+            get_tunnel_health,
+            measure_latency,
+            open_tunnel_url,
+            set_mode,
+            copy_connection_info,
+            copy_tunnel_url,
+            verify_deployment,
+            get_process_diagnostics,
+            tail_log,
+            untail_log,
+            collect_diagnostics,
+            get_effective_config,
+            check_tool_versions,
+            scan_logs_for_errors,
+            get_metrics,
+            reset_metrics,
+            export_diagnostics,
             show_window,
             quit_app,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(running: bool, paused: bool, tunnel_url: Option<&str>) -> SystemStatus {
+        SystemStatus {
+            running,
+            paused,
+            tunnel_url: tunnel_url.map(String::from),
+            started_at: None,
+            uptime_secs: None,
+            processes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn tray_status_shows_the_tunnel_host() {
+        let line = tray_status_line(&status(true, false, Some("https://abc-def.trycloudflare.com/")));
+        assert_eq!(line, "Running - abc-def.trycloudflare.com");
+        assert_eq!(tray_status_line(&status(true, true, Some("https://abc.trycloudflare.com"))), "Paused");
+        assert_eq!(tray_status_line(&status(false, false, None)), "Stopped");
+    }
+
+    #[tokio::test]
+    async fn second_start_is_busy_while_one_is_in_flight() {
+        let lifecycle = Lifecycle::default();
+        let first = lifecycle.try_begin().expect("first start claims the lifecycle");
+        assert!(matches!(lifecycle.try_begin(), Err(error::AppError::Busy)));
+        
+        drop(first);
+        assert!(lifecycle.try_begin().is_ok());
+    }
+
+    #[tokio::test]
+    async fn stop_waits_for_in_flight_start() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        
+        let lifecycle = Lifecycle::default();
+        let finished = AtomicBool::new(false);
+        let guard = lifecycle.try_begin().expect("start claims the lifecycle");
+        
+        let start = async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            finished.store(true, Ordering::SeqCst);
+            drop(guard);
+        };
+        let stop = async {
+            let _guard = lifecycle.begin().await;
+            finished.load(Ordering::SeqCst)
+        };
+        
+        let ((), start_had_finished) = tokio::join!(start, stop);
+        assert!(start_had_finished);
+    }
+
+    #[test]
+    fn saving_a_key_keeps_a_chosen_project_root() {
+        let dir = std::env::temp_dir().join(format!("system-test-chosen-root-{}", bridge::generate_token()));
+        std::fs::create_dir_all(dir.join("cloudflare-agent")).unwrap();
+        let chosen = dir.to_string_lossy().to_string();
+        let mut config = config::Config {
+            anthropic_key: Some("sk-ant-new".into()),
+            project_root: Some(chosen.clone()),
+            ..Default::default()
+        };
+        
+        keep_or_find_project_root(&mut config).unwrap();
+        assert_eq!(config.project_root.as_deref(), Some(chosen.as_str()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn connection_info_masks_only_the_returned_copy() {
+        let secret = "0123456789abcdef0123456789abcdef";
+        let full = connection_info("https://abc.trycloudflare.com", Some(secret));
+        assert_eq!(full, format!("URL: https://abc.trycloudflare.com\nAPI secret: {}", secret));
+        
+        let shown = connection_info("https://abc.trycloudflare.com", Some(&config::redact(secret)));
+        assert!(shown.starts_with("URL: https://abc.trycloudflare.com\n"));
+        assert!(!shown.contains(secret));
+        assert_eq!(connection_info("https://abc.trycloudflare.com", None), "URL: https://abc.trycloudflare.com");
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config;
+use crate::locks::LockExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Serializes read-modify-write of the metrics file across recording threads
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// How often each step outcome happened, e.g. "start_tunnel.timeout" -> 3
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    fn increment(&mut self, event: &str) {
+        *self.counts.entry(event.to_string()).or_insert(0) += 1;
+    }
+}
+
+fn metrics_path() -> Option<PathBuf> {
+    config::get_config_dir().ok().map(|dir| dir.join("metrics.json"))
+}
+
+/// Counts recorded so far (empty when nothing has been recorded)
+pub fn load() -> Metrics {
+    metrics_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Count one occurrence of `event`, if the user opted in
+/// Runs on a background thread so callers never wait on disk
+pub fn record(event: impl Into<String>) {
+    let event = event.into();
+    std::thread::spawn(move || {
+        let enabled = config::load_config().map(|c| c.metrics_enabled()).unwrap_or(false);
+        let Some(path) = metrics_path().filter(|_| enabled) else {
+            return;
+        };
+        
+        let _guard = WRITE_LOCK.lock_or_recover();
+        let mut metrics = load();
+        metrics.increment(&event);
+        if let Ok(content) = serde_json::to_string_pretty(&metrics) {
+            let _ = config::write_file(&path, content);
+        }
+    });
+}
+
+/// Forget everything recorded so far
+pub fn reset() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let _guard = WRITE_LOCK.lock_or_recover();
+    match metrics_path() {
+        Some(path) if path.exists() => Ok(std::fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increments_per_event() {
+        let mut metrics = Metrics::default();
+        metrics.increment("start_tunnel.timeout");
+        metrics.increment("start_tunnel.timeout");
+        metrics.increment("deploy_worker.deploy.failed");
+        
+        assert_eq!(metrics.counts["start_tunnel.timeout"], 2);
+        assert_eq!(metrics.counts["deploy_worker.deploy.failed"], 1);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut metrics = Metrics::default();
+        metrics.increment("start_local_server.ok");
+        let json = serde_json::to_string(&metrics).unwrap();
+        assert_eq!(json, r#"{"counts":{"start_local_server.ok":1}}"#);
+        assert_eq!(serde_json::from_str::<Metrics>(&json).unwrap(), metrics);
+    }
+}
@@ -1,33 +1,254 @@
+use crate::error::AppError;
+use crate::locks::LockExt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Permissions SYSTEM needs, by the names the frontend uses
+pub const PERMISSIONS: &[&str] = &["accessibility", "screen_recording", "automation"];
+
+/// Whether one permission is granted, as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionState {
+    pub name: String,
+    pub granted: bool,
+}
+
+/// How long a system permission check is trusted; short, since the user may be toggling it in Settings
+const PERMISSION_STATUS_TTL: Duration = Duration::from_secs(5);
+
+/// How long a per-app Automation check is trusted; each one is an osascript that can hang on a consent prompt
+const AUTOMATION_STATUS_TTL: Duration = Duration::from_secs(60);
+
+/// Granted/denied results of slow permission probes, each trusted for `ttl`
+struct StatusCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl StatusCache {
+    fn new(ttl: Duration) -> Self {
+        StatusCache { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// The cached result for `key`, unless it was checked more than `ttl` before `now`
+    fn get_at(&self, key: &str, now: Instant) -> Option<bool> {
+        let entries = self.entries.lock_or_recover();
+        entries
+            .get(key)
+            .filter(|(_, checked)| now.saturating_duration_since(*checked) < self.ttl)
+            .map(|(granted, _)| *granted)
+    }
+
+    /// The last result for `key` however old it is, without probing
+    fn last_known(&self, key: &str) -> Option<bool> {
+        self.entries.lock_or_recover().get(key).map(|(granted, _)| *granted)
+    }
+
+    fn insert(&self, key: &str, granted: bool) {
+        self.entries.lock_or_recover().insert(key.to_string(), (granted, Instant::now()));
+    }
+
+    /// Forget `key` so the next lookup probes again
+    fn invalidate(&self, key: &str) {
+        self.entries.lock_or_recover().remove(key);
+    }
+
+    /// The cached result for `key`, running `check` only when there isn't a fresh one
+    fn get_or_check(&self, key: &str, check: impl FnOnce() -> bool) -> bool {
+        if let Some(granted) = self.get_at(key, Instant::now()) {
+            return granted;
+        }
+        let granted = check();
+        self.insert(key, granted);
+        granted
+    }
+}
+
+static PERMISSION_CACHE: Lazy<StatusCache> = Lazy::new(|| StatusCache::new(PERMISSION_STATUS_TTL));
+static AUTOMATION_CACHE: Lazy<StatusCache> = Lazy::new(|| StatusCache::new(AUTOMATION_STATUS_TTL));
+
+/// The check for a permission name, or None for names we don't know
+fn checker(permission: &str) -> Option<fn() -> bool> {
+    match permission {
+        "accessibility" => Some(check_accessibility),
+        "screen_recording" => Some(check_screen_recording),
+        "automation" => Some(check_automation),
+        _ => None,
+    }
+}
 
 /// Check all macOS permissions
 pub fn check_all() -> HashMap<String, bool> {
-    let mut results = HashMap::new();
-    
     // Note: Full Disk Access removed - no longer needed without iMessage
-    results.insert("accessibility".to_string(), check_accessibility());
-    results.insert("screen_recording".to_string(), check_screen_recording());
-    results.insert("automation".to_string(), check_automation());
     // Note: Contacts is handled via Automation permission (AppleScript prompt)
+    PERMISSIONS
+        .iter()
+        .filter_map(|name| checker(name).map(|check| (name.to_string(), PERMISSION_CACHE.get_or_check(name, check))))
+        .collect()
+}
+
+/// Outcome of one check in `check_all_cancellable`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Granted,
+    Denied,
+    /// The check hadn't finished when it was cancelled or ran out of time
+    Unknown,
+}
+
+/// Cancels the `check_all_cancellable` in flight
+static CHECKS_CANCEL: Lazy<Mutex<Arc<AtomicBool>>> = Lazy::new(|| Mutex::new(Arc::new(AtomicBool::new(false))));
+
+/// Run `checks` at once, reporting each through `on_result` as it lands
+/// Stops waiting at `deadline` or when `cancel` is set; anything unfinished comes back `Unknown`
+fn run_checks(
+    checks: Vec<(String, Box<dyn FnOnce() -> bool + Send>)>,
+    deadline: Duration,
+    cancel: &AtomicBool,
+    on_result: impl Fn(&str, bool),
+) -> HashMap<String, CheckStatus> {
+    let mut results: HashMap<String, CheckStatus> = checks.iter().map(|(name, _)| (name.clone(), CheckStatus::Unknown)).collect();
+    let (tx, rx) = mpsc::channel();
+    for (name, check) in checks {
+        let tx = tx.clone();
+        // A hung osascript only ties up its own thread; its late answer goes nowhere
+        std::thread::spawn(move || {
+            let _ = tx.send((name, check()));
+        });
+    }
+    drop(tx);
     
+    let started = Instant::now();
+    let mut pending = results.len();
+    while pending > 0 && !cancel.load(Ordering::Relaxed) {
+        let Some(remaining) = deadline.checked_sub(started.elapsed()) else {
+            break;
+        };
+        // Wake regularly so a cancel is noticed promptly
+        match rx.recv_timeout(remaining.min(Duration::from_millis(50))) {
+            Ok((name, granted)) => {
+                on_result(&name, granted);
+                results.insert(name, if granted { CheckStatus::Granted } else { CheckStatus::Denied });
+                pending -= 1;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
     results
 }
 
+/// Check all permissions at once, giving up after `deadline` or on `cancel_checks`
+/// The onboarding screen gets partial results instead of waiting on a slow machine
+pub fn check_all_cancellable(deadline: Duration, on_result: impl Fn(&str, bool)) -> HashMap<String, CheckStatus> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    *CHECKS_CANCEL.lock_or_recover() = Arc::clone(&cancel);
+    let checks = PERMISSIONS
+        .iter()
+        .filter_map(|&name| {
+            let check = checker(name)?;
+            let run: Box<dyn FnOnce() -> bool + Send> = Box::new(move || PERMISSION_CACHE.get_or_check(name, check));
+            Some((name.to_string(), run))
+        })
+        .collect();
+    run_checks(checks, deadline, &cancel, on_result)
+}
+
+/// Stop waiting on the `check_all_cancellable` in flight
+pub fn cancel_checks() {
+    CHECKS_CANCEL.lock_or_recover().store(true, Ordering::Relaxed);
+}
+
+/// Check a single permission without running the others
+/// Always probes, refreshing the cached result `check_all` uses
+pub fn check(permission: &str) -> Result<PermissionState, AppError> {
+    let check = checker(permission).ok_or_else(|| AppError::UnknownPermission { name: permission.to_string() })?;
+    let granted = check();
+    PERMISSION_CACHE.insert(permission, granted);
+    Ok(PermissionState { name: permission.to_string(), granted })
+}
+
+/// Settings deep link for a permission, plus how to get there by hand if the link doesn't work
+fn settings_pane(permission: &str) -> Option<(&'static str, &'static str)> {
+    match permission {
+        "accessibility" => Some((
+            "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility",
+            "Open System Settings > Privacy & Security > Accessibility and turn on SYSTEM",
+        )),
+        "screen_recording" => Some((
+            "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture",
+            "Open System Settings > Privacy & Security > Screen Recording and turn on SYSTEM",
+        )),
+        "automation" => Some((
+            "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation",
+            "Open System Settings > Privacy & Security > Automation and allow SYSTEM to control each app",
+        )),
+        _ => None,
+    }
+}
+
+/// What happened when we tried to open a permission's Settings pane
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionRequest {
+    /// `open` accepted the deep link
+    pub opened: bool,
+    /// Whether Settings came to the front, or None if that couldn't be checked
+    pub settings_frontmost: Option<bool>,
+    /// Show `manual_steps` because the pane may not have appeared
+    pub show_fallback: bool,
+    pub manual_steps: String,
+}
+
+/// Whether a frontmost app name is System Settings (Ventura+) or System Preferences (older)
+fn is_settings_app(name: &str) -> bool {
+    matches!(name.trim(), "System Settings" | "System Preferences")
+}
+
+/// Name of the frontmost app, if System Events will tell us
+fn frontmost_app() -> Option<String> {
+    let output = Command::new("osascript")
+        .args(["-e", "tell application \"System Events\" to get name of first application process whose frontmost is true"])
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 /// Request a specific permission (opens System Settings)
-pub fn request(permission: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let url = match permission {
-        "accessibility" => "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility",
-        "screen_recording" => "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture",
-        "automation" => "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation",
-        _ => return Err("Unknown permission".into()),
-    };
+/// Deep links have broken between macOS releases, so report whether Settings actually showed up
+pub fn request(permission: &str) -> Result<PermissionRequest, AppError> {
+    let (url, manual_steps) = settings_pane(permission)
+        .ok_or_else(|| AppError::UnknownPermission { name: permission.to_string() })?;
+    let failed = |reason: String| AppError::SettingsOpenFailed { permission: permission.to_string(), reason };
     
-    Command::new("open")
+    let status = Command::new("open")
         .arg(url)
-        .spawn()?;
+        .status()
+        .map_err(|e| failed(e.to_string()))?;
+    if !status.success() {
+        return Err(failed(format!("open exited with {}", status)));
+    }
     
-    Ok(())
+    // The user is about to change it, so don't answer from before they did
+    PERMISSION_CACHE.invalidate(permission);
+    
+    // Give Settings a moment to launch and take focus
+    std::thread::sleep(Duration::from_millis(800));
+    let settings_frontmost = frontmost_app().map(|name| is_settings_app(&name));
+    
+    Ok(PermissionRequest {
+        opened: true,
+        settings_frontmost,
+        show_fallback: settings_frontmost != Some(true),
+        manual_steps: manual_steps.to_string(),
+    })
 }
 
 /// Check Accessibility permission
@@ -55,17 +276,19 @@ fn check_automation() -> bool {
 }
 
 /// Check Screen Recording permission
+/// Asks CoreGraphics rather than capturing anything, so it never prompts or touches the clipboard
+#[cfg(target_os = "macos")]
 fn check_screen_recording() -> bool {
-    // Try to take a screenshot - this is the most reliable way to check
-    // If screen recording is not granted, screencapture will fail or produce empty output
-    let output = Command::new("screencapture")
-        .args(["-x", "-c"]) // -x no sound, -c to clipboard (no file)
-        .output();
-    
-    match output {
-        Ok(o) => o.status.success(),
-        Err(_) => false,
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
     }
+    unsafe { CGPreflightScreenCaptureAccess() }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn check_screen_recording() -> bool {
+    false
 }
 
 /// Apps that need Automation permission
@@ -107,41 +330,699 @@ pub fn check_app_permission(app_name: &str) -> bool {
     }
 }
 
-/// Pre-warm Automation permission for a specific app
-/// Returns true if permission was granted (or already granted), false if denied
-pub fn prewarm_app(app_name: &str) -> bool {
+/// How long a prewarm waits on the consent dialog (or a hung app) before giving up
+const PREWARM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prewarms run at once by default; more and macOS can drop or stack consent dialogs
+pub const PREWARM_CONCURRENCY: usize = 3;
+
+/// How a single app's prewarm ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PrewarmOutcome {
+    Granted,
+    Denied,
+    /// Nothing came back within PREWARM_TIMEOUT; the osascript was killed
+    TimedOut,
+}
+
+/// Pre-warm Automation permission for a specific app, killing the osascript after `timeout`
+fn prewarm_app_within(app_name: &str, timeout: Duration) -> PrewarmOutcome {
     // Find the script for this app
     let script = AUTOMATION_APPS
         .iter()
         .find(|(name, _)| *name == app_name)
         .map(|(_, script)| *script);
     
-    if let Some(script) = script {
-        let output = Command::new("osascript")
-            .args(["-e", script])
-            .output();
-        
-        match output {
-            Ok(o) => o.status.success(),
-            Err(_) => false,
-        }
-    } else {
-        false
+    let outcome = match script.map(|script| output_within(Command::new("osascript").args(["-e", script]), timeout)) {
+        Some(Ok(Some(o))) if o.status.success() => PrewarmOutcome::Granted,
+        Some(Ok(None)) => PrewarmOutcome::TimedOut,
+        _ => PrewarmOutcome::Denied,
+    };
+    
+    // The prompt may have just been answered, so the next status check must look again
+    // A grant is definitive, though, and background completeness checks rely on it
+    AUTOMATION_CACHE.invalidate(app_name);
+    if outcome == PrewarmOutcome::Granted {
+        AUTOMATION_CACHE.insert(app_name, true);
     }
+    outcome
+}
+
+/// Pre-warm Automation permission for a specific app
+/// Returns true if permission was granted (or already granted), false if denied
+pub fn prewarm_app(app_name: &str) -> bool {
+    prewarm_app_within(app_name, PREWARM_TIMEOUT) == PrewarmOutcome::Granted
+}
+
+/// Run `work` over `items` with at most `limit` going at once, reporting each through `on_done` as it finishes
+/// A slow item only holds up its own worker; the others keep taking from the queue
+fn run_bounded<T: Send>(items: &[String], limit: usize, work: impl Fn(&str) -> T + Sync, mut on_done: impl FnMut(&str, T)) {
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, items.len().max(1)) {
+            let (tx, next, work) = (tx.clone(), &next, &work);
+            scope.spawn(move || {
+                while let Some(item) = items.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if tx.send((item.as_str(), work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        for (item, result) in rx {
+            on_done(item, result);
+        }
+    });
+}
+
+/// Prewarm `app_names`, `concurrency` at a time, reporting each app as its prewarm ends
+pub fn prewarm_apps(
+    app_names: &[String],
+    concurrency: usize,
+    mut on_result: impl FnMut(&str, PrewarmOutcome),
+) -> HashMap<String, PrewarmOutcome> {
+    let mut results = HashMap::new();
+    run_bounded(app_names, concurrency, |name| prewarm_app_within(name, PREWARM_TIMEOUT), |name, outcome| {
+        on_result(name, outcome);
+        results.insert(name.to_string(), outcome);
+    });
+    results
 }
 
 /// Get list of apps with their current permission status
+/// Results are cached per app for `AUTOMATION_STATUS_TTL`
 pub fn get_automation_apps_with_status() -> Vec<(String, bool)> {
     AUTOMATION_APPS
         .iter()
         .map(|(name, _)| {
-            let granted = check_app_permission(name);
+            let granted = AUTOMATION_CACHE.get_or_check(name, || check_app_permission(name));
             (name.to_string(), granted)
         })
         .collect()
 }
 
+/// Each app's last known Automation status, without running anything
+/// Probing launches the app and can prompt, so background checks use this; apps never checked count as not granted
+pub fn known_automation_status() -> Vec<(String, bool)> {
+    AUTOMATION_APPS
+        .iter()
+        .map(|(name, _)| (name.to_string(), AUTOMATION_CACHE.last_known(name).unwrap_or(false)))
+        .collect()
+}
+
+/// Re-check one app's Automation status, bypassing the cache
+pub fn refresh_automation_status(app_name: &str) -> bool {
+    AUTOMATION_CACHE.invalidate(app_name);
+    AUTOMATION_CACHE.get_or_check(app_name, || check_app_permission(app_name))
+}
+
 /// Get list of apps that need pre-warming
 pub fn get_automation_apps() -> Vec<String> {
     AUTOMATION_APPS.iter().map(|(name, _)| name.to_string()).collect()
 }
+
+/// How long one self-test operation may run; a consent prompt left open shouldn't hang the wizard
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What an osascript or screencapture says when TCC refuses it, as opposed to some other failure
+const DENIAL_MARKERS: &[&str] = &[
+    "-1743",
+    "Not authorized to send Apple events",
+    "-1719",
+    "-25211",
+    "assistive access",
+    "could not create image from display",
+];
+
+/// How a permission self-test went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SelfTestOutcome {
+    Passed,
+    Denied,
+    /// The permission looks granted, but the real operation still failed
+    Failed,
+    TimedOut,
+}
+
+/// Result of doing one real thing with a permission, as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTest {
+    pub permission: String,
+    pub outcome: SelfTestOutcome,
+    /// What the operation printed when it didn't pass
+    pub detail: Option<String>,
+}
+
+/// The AppleScript a self-test runs: a real read for the apps that have data, the plain probe otherwise
+/// Reads tolerate an empty address book or calendar, which isn't a permission problem
+fn self_test_script(permission: &str) -> Option<&'static str> {
+    match permission {
+        "accessibility" => Some("tell application \"System Events\" to get count of windows of (first application process whose frontmost is true)"),
+        "automation" => Some("tell application \"System Events\" to get name of first application process whose frontmost is true"),
+        "Contacts" => Some("tell application \"Contacts\" to if (count of people) > 0 then get name of first person"),
+        "Calendar" => Some("tell application \"Calendar\" to tell first calendar to if (count of events) > 0 then get summary of first event"),
+        app => AUTOMATION_APPS.iter().find(|(name, _)| *name == app).map(|(_, script)| *script),
+    }
+}
+
+/// Run `cmd`, giving up (and killing it) after `timeout`; None when it timed out
+fn output_within(cmd: &mut Command, timeout: Duration) -> std::io::Result<Option<std::process::Output>> {
+    let child = cmd.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped()).spawn()?;
+    let pid = child.id();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(output) => output.map(Some),
+        Err(_) => {
+            let _ = Command::new("kill").args(["-9", &pid.to_string()]).status();
+            Ok(None)
+        }
+    }
+}
+
+/// Judge a finished operation from its exit status and stderr
+fn judge(success: bool, stderr: &str) -> SelfTestOutcome {
+    if DENIAL_MARKERS.iter().any(|marker| stderr.contains(marker)) {
+        SelfTestOutcome::Denied
+    } else if success {
+        SelfTestOutcome::Passed
+    } else {
+        SelfTestOutcome::Failed
+    }
+}
+
+/// Capture a 1px screenshot to a file and check something was actually written
+fn self_test_screen_recording() -> (SelfTestOutcome, Option<String>) {
+    let path = std::env::temp_dir().join(format!("system-self-test-{}.png", std::process::id()));
+    let result = output_within(
+        Command::new("screencapture").args(["-x", "-R0,0,1,1"]).arg(&path),
+        SELF_TEST_TIMEOUT,
+    );
+    let written = std::fs::metadata(&path).map(|m| m.len() > 0).unwrap_or(false);
+    let _ = std::fs::remove_file(&path);
+    match result {
+        Ok(Some(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            match judge(output.status.success() && written, &stderr) {
+                SelfTestOutcome::Passed => (SelfTestOutcome::Passed, None),
+                outcome if stderr.is_empty() => (outcome, Some("screencapture didn't write an image".to_string())),
+                outcome => (outcome, Some(stderr)),
+            }
+        }
+        Ok(None) => (SelfTestOutcome::TimedOut, None),
+        Err(e) => (SelfTestOutcome::Failed, Some(e.to_string())),
+    }
+}
+
+/// Do one real thing with `permission` (a system permission or an Automation app) and report whether it worked
+/// Something known to be denied isn't run at all, so this never raises a consent prompt for it
+pub fn self_test(permission: &str) -> Result<SelfTest, AppError> {
+    let known_denied = match checker(permission) {
+        Some(check) => !PERMISSION_CACHE.get_or_check(permission, check),
+        None => AUTOMATION_CACHE.last_known(permission) == Some(false),
+    };
+    let (outcome, detail) = if permission == "screen_recording" {
+        if known_denied { (SelfTestOutcome::Denied, None) } else { self_test_screen_recording() }
+    } else {
+        let script = self_test_script(permission).ok_or_else(|| AppError::UnknownPermission { name: permission.to_string() })?;
+        if known_denied {
+            (SelfTestOutcome::Denied, None)
+        } else {
+            match output_within(Command::new("osascript").args(["-e", script]), SELF_TEST_TIMEOUT) {
+                Ok(Some(output)) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                    let outcome = judge(output.status.success(), &stderr);
+                    (outcome, (outcome != SelfTestOutcome::Passed && !stderr.is_empty()).then_some(stderr))
+                }
+                Ok(None) => (SelfTestOutcome::TimedOut, None),
+                Err(e) => (SelfTestOutcome::Failed, Some(e.to_string())),
+            }
+        }
+    };
+    Ok(SelfTest { permission: permission.to_string(), outcome, detail })
+}
+
+/// Whether everything onboarding waits for is granted; anything missing from the results counts as not granted
+pub fn is_complete(
+    permissions: &HashMap<String, bool>,
+    apps: &[(String, bool)],
+    required_permissions: &[String],
+    required_apps: &[String],
+) -> bool {
+    required_permissions.iter().all(|name| permissions.get(name).copied().unwrap_or(false))
+        && required_apps.iter().all(|name| apps.iter().any(|(app, granted)| app == name && *granted))
+}
+
+/// Notices the moment permissions become complete, so it's announced once per transition
+#[derive(Debug, Default)]
+pub struct CompletionTracker {
+    complete: bool,
+}
+
+impl CompletionTracker {
+    /// Record the latest reading; true only when it just went from incomplete to complete
+    pub fn update(&mut self, complete: bool) -> bool {
+        let became_complete = complete && !self.complete;
+        self.complete = complete;
+        became_complete
+    }
+}
+
+/// Order permissions are asked for during onboarding, least intrusive first
+/// Automation is followed by a sub-phase that prompts for each app in `AUTOMATION_APPS`
+pub fn onboarding_order() -> Vec<&'static str> {
+    vec!["automation", "accessibility", "screen_recording"]
+}
+
+/// One thing to ask the user for during onboarding
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "camelCase")]
+pub enum OnboardingStep {
+    /// A system permission, granted in System Settings
+    Permission(String),
+    /// Automation access to a single app, granted from its own prompt
+    AutomationApp(String),
+}
+
+/// What `request_next_missing` did, and how much is left after it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingProgress {
+    /// The step that was opened, or None when everything is granted
+    pub opened: Option<OnboardingStep>,
+    pub remaining: usize,
+}
+
+/// Every required step still missing, in the order they should be asked for
+/// Optional permissions and apps are left for the user to grant whenever they like
+fn missing_steps(
+    granted: &HashMap<String, bool>,
+    apps: &[(String, bool)],
+    required_permissions: &[String],
+    required_apps: &[String],
+) -> Vec<OnboardingStep> {
+    let mut steps = Vec::new();
+    for permission in onboarding_order() {
+        if !required_permissions.iter().any(|p| p == permission) {
+            continue;
+        }
+        if !granted.get(permission).copied().unwrap_or(false) {
+            steps.push(OnboardingStep::Permission(permission.to_string()));
+        }
+        if permission == "automation" {
+            steps.extend(
+                apps.iter()
+                    .filter(|(name, granted)| !granted && required_apps.contains(name))
+                    .map(|(name, _)| OnboardingStep::AutomationApp(name.clone())),
+            );
+        }
+    }
+    steps
+}
+
+/// Each permission's status, split by whether the config requires it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionSummary {
+    /// Every required permission and app is granted
+    pub complete: bool,
+    pub required: HashMap<String, bool>,
+    pub optional: HashMap<String, bool>,
+    pub required_apps: HashMap<String, bool>,
+    pub optional_apps: HashMap<String, bool>,
+}
+
+/// Split check results into what onboarding waits for and what's nice to have
+pub fn summarize(
+    granted: HashMap<String, bool>,
+    apps: Vec<(String, bool)>,
+    required_permissions: &[String],
+    required_apps: &[String],
+) -> PermissionSummary {
+    let complete = is_complete(&granted, &apps, required_permissions, required_apps);
+    let (required, optional) = granted.into_iter().partition(|(name, _)| required_permissions.contains(name));
+    let (required_apps, optional_apps) = apps.into_iter().partition(|(name, _)| required_apps.contains(name));
+    PermissionSummary { complete, required, optional, required_apps, optional_apps }
+}
+
+/// Check everything against the config's required subsets, using the last known Automation results
+pub fn check_required(config: &crate::config::Config) -> PermissionSummary {
+    summarize(
+        check_all(),
+        known_automation_status(),
+        &config.required_permissions(),
+        &config.required_automation_apps(),
+    )
+}
+
+/// Open the prompt for the first missing permission, so the UI can walk the user through one at a time
+pub fn request_next_missing() -> Result<OnboardingProgress, Box<dyn std::error::Error>> {
+    let config = crate::config::load_config().unwrap_or_default();
+    let steps = missing_steps(
+        &check_all(),
+        &get_automation_apps_with_status(),
+        &config.required_permissions(),
+        &config.required_automation_apps(),
+    );
+    let Some(next) = steps.first().cloned() else {
+        return Ok(OnboardingProgress { opened: None, remaining: 0 });
+    };
+    
+    match next {
+        OnboardingStep::Permission(ref name) => {
+            request(name)?;
+        }
+        OnboardingStep::AutomationApp(ref name) => {
+            prewarm_app(name);
+        }
+    }
+    
+    Ok(OnboardingProgress { opened: Some(next), remaining: steps.len() - 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn granted(pairs: &[(&str, bool)]) -> HashMap<String, bool> {
+        pairs.iter().map(|(name, ok)| (name.to_string(), *ok)).collect()
+    }
+
+    fn apps(pairs: &[(&str, bool)]) -> Vec<(String, bool)> {
+        pairs.iter().map(|(name, ok)| (name.to_string(), *ok)).collect()
+    }
+
+    fn all_permissions() -> Vec<String> {
+        PERMISSIONS.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn all_apps() -> Vec<String> {
+        get_automation_apps()
+    }
+
+    #[test]
+    fn order_covers_every_checked_permission() {
+        let mut order = onboarding_order();
+        order.sort();
+        let mut checked = PERMISSIONS.to_vec();
+        checked.sort();
+        assert_eq!(order, checked);
+    }
+
+    #[test]
+    fn every_known_permission_has_a_check() {
+        for name in PERMISSIONS {
+            assert!(checker(name).is_some(), "no check for {}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_permission_is_rejected() {
+        assert!(checker("full_disk_access").is_none());
+        let err = check("camera").unwrap_err();
+        assert!(err.to_string().contains("camera"));
+    }
+
+    #[test]
+    fn every_known_permission_has_a_settings_pane() {
+        for name in PERMISSIONS {
+            let (url, manual) = settings_pane(name).unwrap();
+            assert!(url.starts_with("x-apple.systempreferences:"));
+            assert!(manual.contains("Privacy & Security"));
+        }
+    }
+
+    #[test]
+    fn requesting_unknown_permission_is_its_own_error() {
+        assert!(matches!(request("camera"), Err(AppError::UnknownPermission { .. })));
+    }
+
+    #[test]
+    fn recognises_settings_app_across_versions() {
+        assert!(is_settings_app("System Settings"));
+        assert!(is_settings_app("System Preferences\n"));
+        assert!(!is_settings_app("Finder"));
+    }
+
+    #[test]
+    fn steps_follow_onboarding_order_with_apps_after_automation() {
+        let steps = missing_steps(
+            &granted(&[("accessibility", false), ("screen_recording", false), ("automation", false)]),
+            &apps(&[("Calendar", false), ("Finder", true), ("Notes", false)]),
+            &all_permissions(),
+            &all_apps(),
+        );
+        assert_eq!(steps, vec![
+            OnboardingStep::Permission("automation".into()),
+            OnboardingStep::AutomationApp("Calendar".into()),
+            OnboardingStep::AutomationApp("Notes".into()),
+            OnboardingStep::Permission("accessibility".into()),
+            OnboardingStep::Permission("screen_recording".into()),
+        ]);
+    }
+
+    #[test]
+    fn granted_permissions_are_skipped() {
+        let steps = missing_steps(
+            &granted(&[("accessibility", true), ("screen_recording", false), ("automation", true)]),
+            &apps(&[("Calendar", true)]),
+            &all_permissions(),
+            &all_apps(),
+        );
+        assert_eq!(steps, vec![OnboardingStep::Permission("screen_recording".into())]);
+    }
+
+    #[test]
+    fn nothing_left_when_all_granted() {
+        let steps = missing_steps(
+            &granted(&[("accessibility", true), ("screen_recording", true), ("automation", true)]),
+            &apps(&[("Calendar", true), ("Notes", true)]),
+            &all_permissions(),
+            &all_apps(),
+        );
+        assert!(steps.is_empty());
+    }
+
+    #[test]
+    fn cached_status_is_reused_until_it_expires() {
+        let cache = StatusCache::new(Duration::from_secs(60));
+        let mut probes = 0;
+        assert!(cache.get_or_check("Calendar", || { probes += 1; true }));
+        assert!(cache.get_or_check("Calendar", || { probes += 1; false }));
+        assert_eq!(probes, 1);
+        
+        let later = Instant::now() + Duration::from_secs(61);
+        assert_eq!(cache.get_at("Calendar", later), None);
+    }
+
+    #[test]
+    fn invalidating_one_app_leaves_the_others_cached() {
+        let cache = StatusCache::new(Duration::from_secs(60));
+        cache.insert("Calendar", false);
+        cache.insert("Notes", true);
+        
+        cache.invalidate("Calendar");
+        assert_eq!(cache.get_at("Calendar", Instant::now()), None);
+        assert_eq!(cache.get_at("Notes", Instant::now()), Some(true));
+        
+        // The next lookup probes again and sees the new answer
+        assert!(cache.get_or_check("Calendar", || true));
+    }
+
+    fn timed_checks(checks: &[(&str, u64, bool)]) -> Vec<(String, Box<dyn FnOnce() -> bool + Send>)> {
+        checks
+            .iter()
+            .map(|&(name, millis, granted)| {
+                let run: Box<dyn FnOnce() -> bool + Send> = Box::new(move || {
+                    std::thread::sleep(Duration::from_millis(millis));
+                    granted
+                });
+                (name.to_string(), run)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finished_checks_are_reported_as_they_land() {
+        let seen = Mutex::new(Vec::new());
+        let results = run_checks(
+            timed_checks(&[("accessibility", 0, true), ("automation", 10, false)]),
+            Duration::from_secs(5),
+            &AtomicBool::new(false),
+            |name, _| seen.lock().unwrap().push(name.to_string()),
+        );
+        assert_eq!(results["accessibility"], CheckStatus::Granted);
+        assert_eq!(results["automation"], CheckStatus::Denied);
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn cancelling_returns_promptly_with_partials() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceller = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.store(true, Ordering::Relaxed);
+        });
+        
+        let started = Instant::now();
+        let results = run_checks(
+            timed_checks(&[("accessibility", 0, true), ("screen_recording", 10_000, true)]),
+            Duration::from_secs(30),
+            &cancel,
+            |_, _| {},
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(results["accessibility"], CheckStatus::Granted);
+        assert_eq!(results["screen_recording"], CheckStatus::Unknown);
+    }
+
+    #[test]
+    fn deadline_leaves_slow_checks_unknown() {
+        let results = run_checks(
+            timed_checks(&[("automation", 10_000, true)]),
+            Duration::from_millis(100),
+            &AtomicBool::new(false),
+            |_, _| {},
+        );
+        assert_eq!(results["automation"], CheckStatus::Unknown);
+    }
+
+    #[test]
+    fn completeness_only_counts_the_required_subset() {
+        let permissions = granted(&[("accessibility", true), ("screen_recording", false), ("automation", true)]);
+        let apps = apps(&[("Finder", true), ("Contacts", false)]);
+        let all = vec!["accessibility".to_string(), "screen_recording".to_string(), "automation".to_string()];
+        let some = vec!["accessibility".to_string(), "automation".to_string()];
+        
+        assert!(!is_complete(&permissions, &apps, &all, &[]));
+        assert!(is_complete(&permissions, &apps, &some, &["Finder".to_string()]));
+        assert!(!is_complete(&permissions, &apps, &some, &["Finder".to_string(), "Contacts".to_string()]));
+        // An app that wasn't checked at all isn't granted
+        assert!(!is_complete(&permissions, &apps, &some, &["Notes".to_string()]));
+    }
+
+    #[test]
+    fn completion_fires_once_per_transition() {
+        let mut tracker = CompletionTracker::default();
+        assert!(!tracker.update(false));
+        assert!(tracker.update(true));
+        assert!(!tracker.update(true));
+        // Revoking and granting again is a new transition
+        assert!(!tracker.update(false));
+        assert!(tracker.update(true));
+    }
+
+    #[test]
+    fn optional_permissions_dont_block_onboarding() {
+        let required = vec!["accessibility".to_string(), "automation".to_string()];
+        let granted = granted(&[("accessibility", true), ("screen_recording", false), ("automation", true)]);
+        let apps = apps(&[("Calendar", false), ("Finder", true)]);
+        
+        let steps = missing_steps(&granted, &apps, &required, &["Finder".to_string()]);
+        assert!(steps.is_empty());
+        let steps = missing_steps(&granted, &apps, &required, &all_apps());
+        assert_eq!(steps, vec![OnboardingStep::AutomationApp("Calendar".into())]);
+        
+        let summary = summarize(granted.clone(), apps.clone(), &required, &["Finder".to_string()]);
+        assert!(summary.complete);
+        assert_eq!(summary.optional.get("screen_recording"), Some(&false));
+        assert!(!summary.required.contains_key("screen_recording"));
+        assert_eq!(summary.optional_apps.get("Calendar"), Some(&false));
+        
+        assert!(!summarize(granted, apps, &all_permissions(), &[]).complete);
+    }
+
+    #[test]
+    fn last_known_ignores_expiry() {
+        let cache = StatusCache::new(Duration::ZERO);
+        assert_eq!(cache.last_known("Finder"), None);
+        cache.insert("Finder", true);
+        assert_eq!(cache.get_at("Finder", Instant::now()), None);
+        assert_eq!(cache.last_known("Finder"), Some(true));
+    }
+
+    #[test]
+    fn every_permission_and_app_has_a_self_test() {
+        for name in PERMISSIONS.iter().filter(|name| **name != "screen_recording") {
+            assert!(self_test_script(name).is_some(), "no self-test for {}", name);
+        }
+        for (app, _) in AUTOMATION_APPS {
+            assert!(self_test_script(app).is_some(), "no self-test for {}", app);
+        }
+        assert!(self_test_script("camera").is_none());
+        assert!(matches!(self_test("camera"), Err(AppError::UnknownPermission { .. })));
+    }
+
+    #[test]
+    fn self_test_tells_denial_from_a_broken_operation() {
+        assert_eq!(judge(true, ""), SelfTestOutcome::Passed);
+        assert_eq!(
+            judge(false, "execution error: Not authorized to send Apple events to Contacts. (-1743)"),
+            SelfTestOutcome::Denied
+        );
+        assert_eq!(
+            judge(false, "execution error: System Events got an error: osascript is not allowed assistive access. (-1719)"),
+            SelfTestOutcome::Denied
+        );
+        assert_eq!(judge(false, "execution error: Calendar got an error: AppleEvent timed out. (-1712)"), SelfTestOutcome::Failed);
+    }
+
+    #[test]
+    fn slow_operations_are_cut_off() {
+        let started = Instant::now();
+        let output = output_within(Command::new("sleep").arg("5"), Duration::from_millis(100)).unwrap();
+        assert!(output.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(output_within(&mut Command::new("true"), Duration::from_secs(5)).unwrap().is_some());
+    }
+
+    #[test]
+    fn bounded_runs_stay_under_the_cap_and_route_around_a_slow_item() {
+        let items: Vec<String> = ["hung", "a", "b", "c", "d", "e"].iter().map(|s| s.to_string()).collect();
+        let (running, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let mut finished = Vec::new();
+        run_bounded(
+            &items,
+            2,
+            |item| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(if item == "hung" { 300 } else { 10 }));
+                running.fetch_sub(1, Ordering::SeqCst);
+                item.len()
+            },
+            |item, len| finished.push((item.to_string(), len)),
+        );
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(finished.len(), items.len());
+        // Everything else got through on the other worker while the first item hung
+        assert_eq!(finished.last().unwrap(), &("hung".to_string(), 4));
+    }
+
+    #[test]
+    fn bounded_runs_accept_a_zero_cap_and_no_items() {
+        let items = vec!["a".to_string()];
+        let mut seen = Vec::new();
+        run_bounded(&items, 0, |item| item.to_string(), |_, item| seen.push(item));
+        assert_eq!(seen, vec!["a"]);
+        run_bounded(&[], 3, |_| (), |_, _| panic!("nothing to run"));
+    }
+
+    #[test]
+    fn default_config_is_complete_with_a_cold_automation_cache() {
+        // After a restart nothing has been probed, so every app reads as not granted
+        let config = crate::config::Config::default();
+        let cold: Vec<(String, bool)> = all_apps().into_iter().map(|app| (app, false)).collect();
+        let everything = granted(&PERMISSIONS.iter().map(|p| (*p, true)).collect::<Vec<_>>());
+        assert!(is_complete(&everything, &cold, &config.required_permissions(), &config.required_automation_apps()));
+        assert!(!is_complete(&HashMap::new(), &cold, &config.required_permissions(), &config.required_automation_apps()));
+    }
+}
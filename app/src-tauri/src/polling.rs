@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Poll cadence while the window is shown and focused
+pub const FAST_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Poll cadence while the app sits in the tray
+pub const SLOW_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often a sleeping poller re-checks whether it should wake early
+const WAKE_CHECK: Duration = Duration::from_millis(250);
+
+static POLL_ACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Switch background pollers between the fast and slow cadence
+pub fn set_poll_active(active: bool) {
+    POLL_ACTIVE.store(active, Ordering::Relaxed);
+}
+
+/// Interval background pollers should currently wait between ticks
+pub fn poll_interval() -> Duration {
+    if POLL_ACTIVE.load(Ordering::Relaxed) {
+        FAST_POLL_INTERVAL
+    } else {
+        SLOW_POLL_INTERVAL
+    }
+}
+
+/// First wait when watching for a file to change
+pub const WATCH_START_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest wait between checks when watching for a file to change
+pub const WATCH_MAX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Double a backoff interval, capped at `max`
+pub fn next_backoff(current: Duration, max: Duration) -> Duration {
+    (current * 2).min(max)
+}
+
+/// Block until the next poll is due
+/// Wakes early if the window comes back to the foreground during a slow wait
+pub fn wait_for_next_tick() {
+    let started = Instant::now();
+    while started.elapsed() < poll_interval() {
+        std::thread::sleep(WAKE_CHECK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut interval = WATCH_START_INTERVAL;
+        let mut seen = vec![interval];
+        while interval < WATCH_MAX_INTERVAL {
+            interval = next_backoff(interval, WATCH_MAX_INTERVAL);
+            seen.push(interval);
+        }
+        assert_eq!(seen[1], Duration::from_millis(100));
+        assert_eq!(*seen.last().unwrap(), WATCH_MAX_INTERVAL);
+        assert_eq!(next_backoff(WATCH_MAX_INTERVAL, WATCH_MAX_INTERVAL), WATCH_MAX_INTERVAL);
+    }
+}
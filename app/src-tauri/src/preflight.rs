@@ -0,0 +1,108 @@
+use crate::{bridge, config, permissions};
+use serde::Serialize;
+
+/// Outcome of a single readiness check
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+    pub fix_hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), ok: true, detail: detail.into(), fix_hint: None }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>, fix_hint: &str) -> Self {
+        Self { name: name.to_string(), ok: false, detail: detail.into(), fix_hint: Some(fix_hint.to_string()) }
+    }
+}
+
+/// Command line tools the app shells out to, with how to install each
+const DEPENDENCIES: &[(&str, &str)] = &[
+    ("cloudflared", "Install with `brew install cloudflared`"),
+    ("node", "Install Node.js 18+ from https://nodejs.org"),
+    ("npx", "npx ships with Node.js - reinstall Node.js 18+"),
+];
+
+/// Run the full readiness checklist without starting anything
+pub fn run() -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let config = config::load_config().unwrap_or_default();
+    
+    for (binary, hint) in DEPENDENCIES {
+        let name = format!("dependency:{}", binary);
+        if let Some(path) = bridge::resolve_binary(binary, &config) {
+            results.push(CheckResult::pass(&name, format!("{} found at {}", binary, path.display())));
+        } else {
+            results.push(CheckResult::fail(&name, format!("{} not found on PATH", binary), hint));
+        }
+    }
+    
+    match bridge::find_project_root(Some(&config)) {
+        Ok(root) => match bridge::check_project_compatibility(&root) {
+            Ok(()) => results.push(CheckResult::pass("project_root", root.to_string_lossy())),
+            Err(e) => results.push(CheckResult::fail(
+                "project_root",
+                e.to_string(),
+                "Update the project with `git pull`, then `npm install`",
+            )),
+        },
+        Err(e) => results.push(CheckResult::fail(
+            "project_root",
+            e.to_string(),
+            "Clone the SYSTEM repo into ~/Desktop/system or ~/Projects/system",
+        )),
+    }
+    
+    match config.anthropic_key.as_deref() {
+        Some(key) if config::is_valid_api_key(key) => {
+            results.push(CheckResult::pass("api_key", "Anthropic API key saved"));
+        }
+        Some(_) => results.push(CheckResult::fail(
+            "api_key",
+            "Saved API key doesn't look like an Anthropic key",
+            "Anthropic keys start with sk-ant- - copy it again from console.anthropic.com",
+        )),
+        None => results.push(CheckResult::fail(
+            "api_key",
+            "No Anthropic API key saved",
+            "Enter your key from console.anthropic.com",
+        )),
+    }
+    
+    let required = config.required_permissions();
+    let mut granted = permissions::check_all().into_iter().collect::<Vec<_>>();
+    granted.sort();
+    for (permission, ok) in granted {
+        let name = format!("permission:{}", permission);
+        if ok {
+            results.push(CheckResult::pass(&name, "Granted"));
+        } else if !required.contains(&permission) {
+            results.push(CheckResult::pass(&name, "Not granted (optional)"));
+        } else {
+            results.push(CheckResult::fail(&name, "Not granted", "Grant it in System Settings > Privacy & Security"));
+        }
+    }
+    
+    // Ports are fine if free, or if they're held by processes we already manage
+    for check in bridge::check_ports() {
+        let name = format!("port:{}", check.port);
+        if check.free {
+            results.push(CheckResult::pass(&name, "Free"));
+        } else if check.holder_pid.is_some_and(bridge::is_managed_pid) {
+            results.push(CheckResult::pass(&name, "In use by SYSTEM"));
+        } else {
+            let fix = match check.holder {
+                Some(holder) => format!("Quit `{}` (pid {})", holder, check.holder_pid.unwrap_or_default()),
+                None => format!("Find it with `lsof -i:{}` and quit it", check.port),
+            };
+            results.push(CheckResult::fail(&name, "In use by another process", &fix));
+        }
+    }
+    
+    results
+}
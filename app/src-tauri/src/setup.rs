@@ -0,0 +1,199 @@
+use serde::Serialize;
+
+/// Steps of the first-run wizard, in the order they're completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    ApiKey,
+    Permissions,
+    StartServer,
+    StartTunnel,
+    Deploy,
+    Complete,
+}
+
+impl SetupStep {
+    /// What the user should do next to get past this step
+    pub fn next_action(self) -> &'static str {
+        match self {
+            SetupStep::ApiKey => "Enter your Anthropic API key",
+            SetupStep::Permissions => "Grant the required macOS permissions",
+            SetupStep::StartServer => "Start the local server",
+            SetupStep::StartTunnel => "Start the secure tunnel",
+            SetupStep::Deploy => "Deploy the worker to Cloudflare",
+            SetupStep::Complete => "Nothing to do - SYSTEM is ready",
+        }
+    }
+}
+
+/// Everything the current step depends on
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetupInputs {
+    pub has_api_key: bool,
+    pub permissions_granted: bool,
+    pub server_running: bool,
+    pub tunnel_running: bool,
+    pub wants_deploy: bool,
+    pub deployed: bool,
+}
+
+/// Where the wizard should resume, as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupState {
+    pub step: SetupStep,
+    pub next_action: String,
+    pub setup_complete: bool,
+}
+
+/// The first step that isn't satisfied yet
+pub fn derive_step(inputs: SetupInputs) -> SetupStep {
+    if !inputs.has_api_key {
+        SetupStep::ApiKey
+    } else if !inputs.permissions_granted {
+        SetupStep::Permissions
+    } else if !inputs.server_running {
+        SetupStep::StartServer
+    } else if !inputs.tunnel_running {
+        SetupStep::StartTunnel
+    } else if inputs.wants_deploy && !inputs.deployed {
+        SetupStep::Deploy
+    } else {
+        SetupStep::Complete
+    }
+}
+
+/// The single choice the user makes: everything on this Mac, or a deployed worker reaching it through the tunnel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Local,
+    Remote,
+}
+
+impl Mode {
+    pub fn parse(value: &str) -> Result<Mode, String> {
+        match value.trim() {
+            "local" => Ok(Mode::Local),
+            "remote" => Ok(Mode::Remote),
+            other => Err(format!("Unknown mode \"{}\" - choose \"local\" or \"remote\"", other)),
+        }
+    }
+
+    /// The value saved in the config's `mode`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Local => "local",
+            Mode::Remote => "remote",
+        }
+    }
+}
+
+/// What a mode switch starts from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModeInputs {
+    pub has_api_key: bool,
+    pub deployed: bool,
+    pub has_deployed_url: bool,
+}
+
+/// The steps a mode switch takes after restarting the local server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeTransition {
+    pub target: Mode,
+    pub start_tunnel: bool,
+    /// No finished deploy to reuse, so the worker is deployed as part of the switch
+    pub deploy: bool,
+}
+
+/// Work out how to reach `target`, refusing switches that can't end in a consistent state
+pub fn plan_mode_change(inputs: ModeInputs, target: &str) -> Result<ModeTransition, String> {
+    let target = Mode::parse(target)?;
+    if !inputs.has_api_key {
+        return Err("Enter your Anthropic API key before choosing a mode".to_string());
+    }
+    Ok(match target {
+        Mode::Local => ModeTransition { target, start_tunnel: false, deploy: false },
+        // A half-finished deploy is redone rather than trusted
+        Mode::Remote => ModeTransition { target, start_tunnel: true, deploy: !(inputs.deployed && inputs.has_deployed_url) },
+    })
+}
+
+/// Only a finished deploy may be recorded as remote mode, so `remote` never sits next to `deployed: false`
+pub fn check_remote_ready(inputs: ModeInputs) -> Result<(), String> {
+    if inputs.deployed && inputs.has_deployed_url {
+        Ok(())
+    } else {
+        Err("The worker isn't fully deployed, so remote mode wasn't turned on - deploy the worker and try again".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_first_unsatisfied_step() {
+        let all = SetupInputs {
+            has_api_key: true,
+            permissions_granted: true,
+            server_running: true,
+            tunnel_running: true,
+            wants_deploy: true,
+            deployed: true,
+        };
+        let cases = [
+            (SetupInputs::default(), SetupStep::ApiKey),
+            (SetupInputs { has_api_key: false, ..all }, SetupStep::ApiKey),
+            (SetupInputs { permissions_granted: false, ..all }, SetupStep::Permissions),
+            (SetupInputs { permissions_granted: false, server_running: false, ..all }, SetupStep::Permissions),
+            (SetupInputs { server_running: false, ..all }, SetupStep::StartServer),
+            (SetupInputs { tunnel_running: false, ..all }, SetupStep::StartTunnel),
+            (SetupInputs { deployed: false, ..all }, SetupStep::Deploy),
+            (SetupInputs { wants_deploy: false, deployed: false, ..all }, SetupStep::Complete),
+            (all, SetupStep::Complete),
+        ];
+        
+        for (inputs, expected) in cases {
+            assert_eq!(derive_step(inputs), expected, "inputs: {:?}", inputs);
+        }
+    }
+
+    #[test]
+    fn invalid_mode_switches_are_refused() {
+        let ready = ModeInputs { has_api_key: true, deployed: true, has_deployed_url: true };
+        assert!(plan_mode_change(ready, "cloud").unwrap_err().contains("cloud"));
+        // local-only is an advanced config setting, not one of the two choices
+        assert!(plan_mode_change(ready, "local-only").is_err());
+        assert!(plan_mode_change(ModeInputs { has_api_key: false, ..ready }, "local").is_err());
+        assert!(plan_mode_change(ModeInputs { has_api_key: false, ..ready }, "remote").is_err());
+    }
+
+    #[test]
+    fn remote_deploys_unless_a_finished_deploy_exists() {
+        let key = ModeInputs { has_api_key: true, ..Default::default() };
+        let cases = [
+            (key, true),
+            (ModeInputs { has_deployed_url: true, ..key }, true),
+            (ModeInputs { deployed: true, ..key }, true),
+            (ModeInputs { deployed: true, has_deployed_url: true, ..key }, false),
+        ];
+        for (inputs, deploy) in cases {
+            let transition = plan_mode_change(inputs, "remote").unwrap();
+            assert_eq!(transition, ModeTransition { target: Mode::Remote, start_tunnel: true, deploy }, "inputs: {:?}", inputs);
+        }
+        assert_eq!(
+            plan_mode_change(key, " local ").unwrap(),
+            ModeTransition { target: Mode::Local, start_tunnel: false, deploy: false }
+        );
+    }
+
+    #[test]
+    fn remote_is_only_recorded_after_a_finished_deploy() {
+        let key = ModeInputs { has_api_key: true, ..Default::default() };
+        assert!(check_remote_ready(key).is_err());
+        assert!(check_remote_ready(ModeInputs { has_deployed_url: true, ..key }).is_err());
+        assert!(check_remote_ready(ModeInputs { deployed: true, ..key }).is_err());
+        assert!(check_remote_ready(ModeInputs { deployed: true, has_deployed_url: true, ..key }).is_ok());
+    }
+}
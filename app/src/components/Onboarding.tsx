@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-shell';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 
@@ -16,6 +17,20 @@ interface Permission {
   granted: boolean;
 }
 
+interface TunnelInfo {
+  success: boolean;
+  url: string | null;
+  apiSecret: string | null;
+  error: string | null;
+  cancelled: boolean;
+  outcome: 'started' | 'alreadyRunning' | null;
+}
+
+interface ServerStart {
+  outcome: 'started' | 'alreadyRunning';
+  apiSecret: string | null;
+}
+
 interface AppPermission {
   name: string;
   icon: string;
@@ -90,11 +105,13 @@ export default function Onboarding({ onComplete }: OnboardingProps) {
     
     try {
       setStatusMessage('Starting local server...');
-      const generatedSecret = await invoke<string>('start_local_server');
-      setApiSecret(generatedSecret);
+      const server = await invoke<ServerStart>('start_local_server');
+      if (server.apiSecret) {
+        setApiSecret(server.apiSecret);
+      }
       
       setStatusMessage('Creating secure tunnel...');
-      const result = await invoke<{ success: boolean; url?: string; apiSecret?: string; error?: string }>('start_tunnel');
+      const result = await invoke<TunnelInfo>('start_tunnel');
       
       if (result.success && result.url) {
         setTunnelUrl(result.url);
@@ -132,25 +149,28 @@ export default function Onboarding({ onComplete }: OnboardingProps) {
       .map((app, idx) => ({ ...app, idx }))
       .filter(app => app.status === 'pending');
     
-    for (const app of pendingApps) {
-      setCurrentAppIndex(app.idx);
-      setAppPermissions(prev => prev.map((a, idx) => 
-        idx === app.idx ? { ...a, status: 'checking' } : a
+    if (pendingApps.length === 0) return;
+    
+    const pendingNames = new Set(pendingApps.map(app => app.name));
+    setCurrentAppIndex(pendingApps[0].idx);
+    setAppPermissions(prev => prev.map(a => 
+      pendingNames.has(a.name) ? { ...a, status: 'checking' } : a
+    ));
+    
+    // The backend runs a few at a time and reports each app as it finishes
+    const unlisten = await listen<{ app: string; outcome: string }>('system://prewarm-progress', event => {
+      setAppPermissions(prev => prev.map(a => 
+        a.name === event.payload.app ? { ...a, status: event.payload.outcome === 'granted' ? 'granted' : 'denied' } : a
       ));
-      
-      try {
-        const granted = await invoke<boolean>('prewarm_app', { appName: app.name });
-        setAppPermissions(prev => prev.map((a, idx) => 
-          idx === app.idx ? { ...a, status: granted ? 'granted' : 'denied' } : a
-        ));
-      } catch (e) {
-        setAppPermissions(prev => prev.map((a, idx) => 
-          idx === app.idx ? { ...a, status: 'denied' } : a
-        ));
-      }
-      
-      // Small delay between apps so user can see progress
-      await new Promise(resolve => setTimeout(resolve, 300));
+    });
+    try {
+      await invoke('prewarm_apps', { appNames: [...pendingNames] });
+    } catch (e) {
+      setAppPermissions(prev => prev.map(a => 
+        a.status === 'checking' ? { ...a, status: 'denied' } : a
+      ));
+    } finally {
+      unlisten();
     }
     setCurrentAppIndex(-1);
   }
@@ -187,12 +207,18 @@ export default function Onboarding({ onComplete }: OnboardingProps) {
       await invoke('save_api_key', { apiKey });
       
       setStatusMessage('Starting local server...');
-      // start_local_server now returns the generated API secret
-      const generatedSecret = await invoke<string>('start_local_server');
-      setApiSecret(generatedSecret);
+      // start_local_server returns the session's API secret, whether it started or was already up
+      const server = await invoke<ServerStart>('start_local_server');
+      if (server.apiSecret) {
+        setApiSecret(server.apiSecret);
+      }
+      if (server.outcome === 'alreadyRunning') {
+        // The running worker still has the old key until .dev.vars is rewritten
+        await invoke('sync_dev_vars');
+      }
       
       setStatusMessage('Creating secure tunnel...');
-      const result = await invoke<{ success: boolean; url?: string; apiSecret?: string; error?: string }>('start_tunnel');
+      const result = await invoke<TunnelInfo>('start_tunnel');
       
       if (result.success && result.url) {
         setTunnelUrl(result.url);
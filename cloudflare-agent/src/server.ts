@@ -17,12 +17,18 @@ export interface Env {
   BRIDGE_URL: string;
   BRIDGE_AUTH_TOKEN: string;
   API_SECRET: string;
+  ANTHROPIC_BASE_URL?: string; // Gateway/proxy in front of api.anthropic.com
   SystemAgent: DurableObjectNamespace<SystemAgent>;
   // Model configuration (optional - defaults provided)
   MODEL_FAST?: string;  // For quick tasks like intent classification
   MODEL_SMART?: string; // For complex reasoning and vision
 }
 
+function anthropicMessagesUrl(env: Env): string {
+  const base = (env.ANTHROPIC_BASE_URL || "https://api.anthropic.com").replace(/\/+$/, "");
+  return `${base}/v1/messages`;
+}
+
 type MessageContent = 
   | { type: "text"; text: string }
   | { type: "image"; source: { type: "base64"; media_type: string; data: string } };
@@ -1140,7 +1146,7 @@ User request: "${message.replace(/"/g, '\\"')}"
 Category:`;
 
     try {
-      const response = await fetch("https://api.anthropic.com/v1/messages", {
+      const response = await fetch(anthropicMessagesUrl(this.env), {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -1163,7 +1169,7 @@ Category:`;
   }
 
   async callClaude(systemPrompt: string, messages: Message[]): Promise<string> {
-    const response = await fetch("https://api.anthropic.com/v1/messages", {
+    const response = await fetch(anthropicMessagesUrl(this.env), {
       method: "POST",
       headers: {
         "Content-Type": "application/json",
@@ -1207,7 +1213,7 @@ Category:`;
       }
     ];
 
-    const response = await fetch("https://api.anthropic.com/v1/messages", {
+    const response = await fetch(anthropicMessagesUrl(this.env), {
       method: "POST",
       headers: {
         "Content-Type": "application/json",
@@ -1292,10 +1298,19 @@ export default {
       return Response.json({
         name: "SYSTEM",
         version: "1.0.0",
-        endpoints: ["/", "/agents/system-agent/chat", "/agents/system-agent/schedules"],
+        endpoints: ["/", "/api/status", "/agents/system-agent/chat", "/agents/system-agent/schedules"],
       });
     }
     
+    // Deployment status, so the desktop app can tell whether this worker matches its local setup
+    if (url.pathname === "/api/status" && request.method === "GET") {
+      const token = request.headers.get("Authorization")?.replace("Bearer ", "");
+      if (!token || !timingSafeEqual(token, env.API_SECRET)) {
+        return Response.json({ error: "Unauthorized" }, { status: 401 });
+      }
+      return Response.json({ version: "1.0.0", bridgeUrl: env.BRIDGE_URL });
+    }
+    
     // Agent routes
     if (url.pathname.startsWith("/agents/")) {
       const authHeader = request.headers.get("Authorization");